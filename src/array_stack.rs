@@ -0,0 +1,403 @@
+use std::{
+    fmt::{
+        self,
+        Debug,
+        Formatter,
+    },
+    ops::{
+        Index,
+        IndexMut,
+    },
+    iter::Rev,
+    mem::{
+        ManuallyDrop,
+        MaybeUninit,
+    },
+};
+use crate::stack::{
+    StackOverflow,
+    StackUnderflow,
+    StackError,
+};
+
+
+/// A [`crate::stack::Stack`] that holds at most `N` items inline and never allocates. Pushing
+/// onto a full stack fails instead of growing it.
+pub struct ArrayStack<T, const N: usize> {
+    /// Only the first `len` items are initialized
+    items: [MaybeUninit<T>; N],
+    len: usize,
+}
+impl<T, const N: usize> ArrayStack<T, N> {
+    /// All the items, with the bottom of the stack first
+    pub fn as_slice(&self)->&[T] {
+        // SAFETY: the first `len` items are initialized
+        unsafe {std::slice::from_raw_parts(self.items.as_ptr().cast::<T>(), self.len)}
+    }
+
+    pub fn as_mut_slice(&mut self)->&mut [T] {
+        // SAFETY: the first `len` items are initialized
+        unsafe {std::slice::from_raw_parts_mut(self.items.as_mut_ptr().cast::<T>(), self.len)}
+    }
+
+    /// Creates a new stack
+    pub fn new()->Self {
+        ArrayStack {
+            items: [const {MaybeUninit::uninit()}; N],
+            len: 0,
+        }
+    }
+
+    /// Clears the stack
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+
+    /// Pushes the item onto the stack. Returns the item back if the stack is full.
+    pub fn try_push(&mut self, item: T)->Result<(), StackOverflow<T>> {
+        if self.len == N {
+            return Err(StackOverflow {
+                item,
+                limit: N,
+            });
+        }
+
+        self.items[self.len].write(item);
+        self.len += 1;
+
+        return Ok(());
+    }
+
+    /// Pushes the item onto the stack or panics if the stack is full
+    pub fn push(&mut self, item: T) {
+        if self.try_push(item).is_err() {
+            panic!("ArrayStack is full");
+        }
+    }
+
+    pub fn pop(&mut self)->Option<T> {
+        if self.len == 0 {return None}
+
+        self.len -= 1;
+
+        // SAFETY: the item was initialized, and is now past `len` so it won't be read again
+        return Some(unsafe {self.items[self.len].assume_init_read()});
+    }
+
+    /// Pops the top `n` items into a new stack, keeping their order. Returns `None` and leaves the
+    /// stack alone if there are fewer than `n` items.
+    pub fn pop_n(&mut self, n: usize)->Option<Self> {
+        let start = self.len.checked_sub(n)?;
+
+        let mut top = Self::new();
+        for item in self.items[start..self.len].iter() {
+            // SAFETY: the item is initialized, and `len` is moved below it right after, so it is
+            // only owned by `top`
+            top.items[top.len].write(unsafe {item.assume_init_read()});
+            top.len += 1;
+        }
+        self.len = start;
+
+        return Some(top);
+    }
+
+    /// Same as [`Self::pop_n`], but returns an array
+    pub fn pop_array<const M: usize>(&mut self)->Option<[T; M]> {
+        // yields from the bottom up, which is the order the array is filled in
+        let mut top = self.pop_n(M)?.into_iter();
+        return Some(std::array::from_fn(|_|top.next_back().unwrap()));
+    }
+
+    /// Removes the top `n` items, yielding them in the order they were pushed. Returns `None` and
+    /// leaves the stack alone if there are fewer than `n` items.
+    pub fn drain_top(&mut self, n: usize)->Option<Rev<ArrayStackIntoIter<T, N>>> {
+        let top = self.pop_n(n)?;
+        return Some(top.into_iter().rev());
+    }
+
+    /// Pops items until there are at most `len` left
+    pub fn truncate_to(&mut self, len: usize) {
+        while self.len > len {
+            self.pop();
+        }
+    }
+
+    fn check_len(&self, needed: usize)->Result<(), StackUnderflow> {
+        if self.len < needed {
+            return Err(StackUnderflow {
+                needed,
+                len: self.len,
+            });
+        }
+
+        return Ok(());
+    }
+
+    /// Pushes a copy of the top item: `a -> a a`
+    pub fn dup(&mut self)->Result<(), StackError> where T: Clone {
+        self.pick(0)
+    }
+
+    /// Swaps the top two items: `a b -> b a`
+    pub fn swap(&mut self)->Result<(), StackUnderflow> {
+        self.check_len(2)?;
+        let len = self.len;
+        self.as_mut_slice().swap(len - 1, len - 2);
+
+        return Ok(());
+    }
+
+    /// Pushes a copy of the second item: `a b -> a b a`
+    pub fn over(&mut self)->Result<(), StackError> where T: Clone {
+        self.pick(1)
+    }
+
+    /// Moves the third item to the top: `a b c -> b c a`
+    pub fn rot(&mut self)->Result<(), StackUnderflow> {
+        self.check_len(3)?;
+        let len = self.len;
+        self.as_mut_slice()[len - 3..].rotate_left(1);
+
+        return Ok(());
+    }
+
+    /// Pushes a copy of the item `depth` items down from the top. `pick(0)` is the same as
+    /// [`Self::dup`] and `pick(1)` is the same as [`Self::over`]. Fails if there are too few items
+    /// or the stack is full.
+    pub fn pick(&mut self, depth: usize)->Result<(), StackError> where T: Clone {
+        self.check_len(depth.saturating_add(1))?;
+        let item = self.as_slice()[self.len - depth - 1].clone();
+        self.try_push(item).map_err(|e|StackError::Overflow{limit: e.limit})?;
+
+        return Ok(());
+    }
+
+    pub fn last(&self)->Option<&T> {
+        self.as_slice().last()
+    }
+
+    pub fn last_mut(&mut self)->Option<&mut T> {
+        self.as_mut_slice().last_mut()
+    }
+
+    /// Gets the item `depth` items down from the top, where `0` is the top. Returns `None` past
+    /// the bottom of the stack.
+    pub fn get(&self, depth: usize)->Option<&T> {
+        let index = self.len.checked_sub(depth)?.checked_sub(1)?;
+        return self.as_slice().get(index);
+    }
+
+    pub fn get_mut(&mut self, depth: usize)->Option<&mut T> {
+        let index = self.len.checked_sub(depth)?.checked_sub(1)?;
+        return self.as_mut_slice().get_mut(index);
+    }
+
+    /// The top `n` items, with the top of the stack last. Returns `None` if there are fewer than
+    /// `n` items.
+    pub fn peek_n(&self, n: usize)->Option<&[T]> {
+        let start = self.len.checked_sub(n)?;
+        return Some(&self.as_slice()[start..]);
+    }
+
+    pub fn contains(&self, item: &T)->bool where T: PartialEq {
+        self.as_slice().contains(item)
+    }
+
+    /// The depth from the top of the nearest item matching the predicate
+    pub fn position<F: FnMut(&T)->bool>(&self, f: F)->Option<usize> {
+        self.iter().position(f)
+    }
+
+    /// The nearest item to the top matching the predicate
+    pub fn find<F: FnMut(&T)->bool>(&self, mut f: F)->Option<&T> {
+        self.iter().find(|t|f(t))
+    }
+
+    /// Iterate from the top of the stack to the bottom.
+    pub fn iter<'a>(&'a self)->impl 'a + Iterator<Item = &'a T> {
+        self.as_slice().iter().rev()
+    }
+
+    /// Iterate from the top of the stack to the bottom, mutably.
+    pub fn iter_mut<'a>(&'a mut self)->impl 'a + Iterator<Item = &'a mut T> {
+        self.as_mut_slice().iter_mut().rev()
+    }
+
+    pub fn len(&self)->usize {
+        self.len
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self)->bool {
+        self.len == N
+    }
+
+    /// The maximum number of items the stack can hold
+    pub fn capacity(&self)->usize {
+        N
+    }
+}
+impl<T, const N: usize> Drop for ArrayStack<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+impl<T: Clone, const N: usize> Clone for ArrayStack<T, N> {
+    fn clone(&self)->Self {
+        let mut stack = Self::new();
+        for item in self.as_slice() {
+            stack.push(item.clone());
+        }
+        return stack;
+    }
+}
+/// Pushes the items in order, so the last one ends up on top. Panics if there are more than `N`.
+impl<T, const N: usize> FromIterator<T> for ArrayStack<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I)->Self {
+        let mut stack = Self::new();
+        stack.extend(iter);
+        return stack;
+    }
+}
+/// Panics if the stack fills up, like [`ArrayStack::push`]
+impl<T, const N: usize> Extend<T> for ArrayStack<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+/// Yields the items from the top of the stack to the bottom
+impl<T, const N: usize> IntoIterator for ArrayStack<T, N> {
+    type Item = T;
+    type IntoIter = ArrayStackIntoIter<T, N>;
+    fn into_iter(self)->Self::IntoIter {
+        let stack = ManuallyDrop::new(self);
+
+        ArrayStackIntoIter {
+            // SAFETY: `stack` is never dropped, so the items are only owned by the iterator
+            items: unsafe {std::ptr::read(&stack.items)},
+            bottom: 0,
+            top: stack.len,
+        }
+    }
+}
+impl<T, const N: usize> Default for ArrayStack<T, N> {
+    fn default()->Self {
+        Self::new()
+    }
+}
+/// Shows the items from the bottom of the stack to the top
+impl<T: Debug, const N: usize> Debug for ArrayStack<T, N> {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        f.debug_list()
+            .entries(self.as_slice())
+            .finish()
+    }
+}
+impl<T: PartialEq, const N: usize> PartialEq for ArrayStack<T, N> {
+    fn eq(&self, other: &Self)->bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+impl<T, const N: usize> Index<usize> for ArrayStack<T, N> {
+    type Output = T;
+    fn index(&self, index: usize)->&T {
+        self.get(index).expect("ArrayStack index is past the bottom of the stack")
+    }
+}
+impl<T, const N: usize> IndexMut<usize> for ArrayStack<T, N> {
+    fn index_mut(&mut self, index: usize)->&mut T {
+        self.get_mut(index).expect("ArrayStack index is past the bottom of the stack")
+    }
+}
+
+
+/// Yields the items of an [`ArrayStack`] from the top to the bottom
+pub struct ArrayStackIntoIter<T, const N: usize> {
+    /// Only the items in `bottom..top` are initialized
+    items: [MaybeUninit<T>; N],
+    bottom: usize,
+    top: usize,
+}
+impl<T, const N: usize> Iterator for ArrayStackIntoIter<T, N> {
+    type Item = T;
+    fn next(&mut self)->Option<T> {
+        if self.bottom == self.top {return None}
+
+        self.top -= 1;
+
+        // SAFETY: the item was initialized, and is now outside `bottom..top`
+        return Some(unsafe {self.items[self.top].assume_init_read()});
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        let len = self.top - self.bottom;
+        (len, Some(len))
+    }
+}
+impl<T, const N: usize> DoubleEndedIterator for ArrayStackIntoIter<T, N> {
+    fn next_back(&mut self)->Option<T> {
+        if self.bottom == self.top {return None}
+
+        self.bottom += 1;
+
+        // SAFETY: the item was initialized, and is now outside `bottom..top`
+        return Some(unsafe {self.items[self.bottom - 1].assume_init_read()});
+    }
+}
+impl<T, const N: usize> ExactSizeIterator for ArrayStackIntoIter<T, N> {}
+impl<T, const N: usize> Drop for ArrayStackIntoIter<T, N> {
+    fn drop(&mut self) {
+        while self.next().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_past_the_bottom_is_none() {
+        let mut stack = ArrayStack::<i32, 2>::new();
+        stack.push(1);
+
+        assert_eq!(stack.get(0), Some(&1));
+        assert_eq!(stack.get(1), None);
+        assert_eq!(stack.get(usize::MAX), None);
+        assert_eq!(stack.get_mut(usize::MAX), None);
+    }
+
+    #[test]
+    fn forth_words() {
+        let mut stack = ArrayStack::<i32, 4>::from_iter([1, 2, 3]);
+
+        stack.rot().unwrap();
+        assert_eq!(stack.as_slice(), [2, 3, 1]);
+        stack.swap().unwrap();
+        assert_eq!(stack.as_slice(), [2, 1, 3]);
+        stack.over().unwrap();
+        assert_eq!(stack.as_slice(), [2, 1, 3, 1]);
+        assert_eq!(stack.dup(), Err(StackError::Overflow {limit: 4}));
+        assert_eq!(stack.pick(9), Err(StackError::Underflow(StackUnderflow {needed: 10, len: 4})));
+
+        assert_eq!(stack.pop_array::<2>(), Some([3, 1]));
+        assert_eq!(stack.drain_top(2).unwrap().collect::<Vec<_>>(), [2, 1]);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn into_iter_drops_the_rest() {
+        let item = std::rc::Rc::new(());
+        let stack = ArrayStack::<_, 4>::from_iter([item.clone(), item.clone(), item.clone()]);
+
+        let mut iter = stack.into_iter();
+        iter.next();
+        drop(iter);
+
+        assert_eq!(std::rc::Rc::strong_count(&item), 1);
+    }
+}
@@ -0,0 +1,113 @@
+//! A simple growable bitset used to track which slots of a container are occupied so scans can
+//! skip 64 empty slots at a time.
+
+
+const BITS: usize = u64::BITS as usize;
+
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Bitmap {
+    words: Vec<u64>,
+}
+impl Bitmap {
+    #[inline]
+    fn word_count(bits: usize)->usize {
+        bits.div_ceil(BITS)
+    }
+
+    pub fn new()->Self {
+        Bitmap {
+            words: Vec::new(),
+        }
+    }
+
+    /// Creates a bitmap that can hold `bits` bits without reallocating
+    pub fn with_capacity(bits: usize)->Self {
+        Bitmap {
+            words: Vec::with_capacity(Self::word_count(bits)),
+        }
+    }
+
+    /// Makes sure `bits` bits can be held without reallocating
+    pub fn reserve_bits(&mut self, bits: usize) {
+        let words = Self::word_count(bits);
+        self.words.reserve(words.saturating_sub(self.words.len()));
+    }
+
+    /// If setting bit `i` would not need to reallocate
+    pub fn can_set_without_alloc(&self, i: usize)->bool {
+        i / BITS < self.words.capacity()
+    }
+
+    pub fn set(&mut self, i: usize) {
+        let word = i / BITS;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << (i % BITS);
+    }
+
+    pub fn unset(&mut self, i: usize) {
+        if let Some(word) = self.words.get_mut(i / BITS) {
+            *word &= !(1 << (i % BITS));
+        }
+    }
+
+    pub fn get(&self, i: usize)->bool {
+        match self.words.get(i / BITS) {
+            Some(word)=>word & (1 << (i % BITS)) != 0,
+            None=>false,
+        }
+    }
+
+    /// Unsets every bit, but keeps the allocation
+    pub fn clear(&mut self) {
+        self.words.clear();
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        while let Some(0) = self.words.last() {
+            self.words.pop();
+        }
+        self.words.shrink_to_fit();
+    }
+
+    /// Finds the first set bit at or after `from`. Skips whole words of unset bits at a time.
+    pub fn next_set(&self, from: usize)->Option<usize> {
+        let mut word_index = from / BITS;
+        let mut word = *self.words.get(word_index)? & (!0 << (from % BITS));
+
+        loop {
+            if word != 0 {
+                return Some(word_index * BITS + word.trailing_zeros() as usize);
+            }
+
+            word_index += 1;
+            word = *self.words.get(word_index)?;
+        }
+    }
+
+    /// Finds the last set bit before `before`
+    pub fn prev_set(&self, before: usize)->Option<usize> {
+        if before == 0 {return None}
+
+        let last = before - 1;
+        let mut word_index = last / BITS;
+        let mut word = match self.words.get(word_index) {
+            Some(word)=>word & (!0 >> (BITS - 1 - last % BITS)),
+            None=>{
+                word_index = self.words.len().checked_sub(1)?;
+                self.words[word_index]
+            },
+        };
+
+        loop {
+            if word != 0 {
+                return Some(word_index * BITS + (BITS - 1 - word.leading_zeros() as usize));
+            }
+
+            word_index = word_index.checked_sub(1)?;
+            word = self.words[word_index];
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    fmt::{
+        self,
+        Debug,
+        Formatter,
+    },
+    ops::{
+        Index,
+        IndexMut,
+    },
+    cell::{
+        Cell,
+        UnsafeCell,
+    },
+    marker::PhantomData,
+};
+use crate::Key;
+
+
+const DEFAULT_CHUNK_SIZE: usize = 64;
+
+
+/// A [`crate::keyed_vec::KeyedVec`] that stores its data in fixed-size chunks that are never
+/// moved, so `insert` only needs `&self` and never invalidates references to the existing data.
+/// Unlike `KeyedVec`, nothing can be removed, not even from the end.
+///
+/// Since inserting goes through a shared reference, this is `Send` but not `Sync`.
+pub struct ChunkedKeyedVec<K: Key, T> {
+    /// Every chunk has a capacity of `chunk_size` and is never pushed past it, so the data inside
+    /// never moves. Only the list of chunks itself reallocates.
+    chunks: UnsafeCell<Vec<Vec<T>>>,
+    len: Cell<usize>,
+    chunk_size: usize,
+    _phantom: PhantomData<K>,
+}
+impl<K: Key, T> ChunkedKeyedVec<K, T> {
+    #[inline]
+    fn split_id(&self, id: usize)->(usize, usize) {
+        (id / self.chunk_size, id % self.chunk_size)
+    }
+
+    fn chunks(&self)->&Vec<Vec<T>> {
+        // SAFETY: the only mutable access through a shared reference is in `insert`, which never
+        // lets its reference escape and only writes to slots past the end of the data.
+        unsafe {&*self.chunks.get()}
+    }
+
+    pub fn new()->Self {
+        Self::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Creates a vec that allocates room for `size` items at a time.
+    ///
+    /// Panics if `size` is zero.
+    pub fn with_chunk_size(size: usize)->Self {
+        assert!(size > 0, "ChunkedKeyedVec chunks need room for at least one item");
+
+        ChunkedKeyedVec {
+            chunks: UnsafeCell::new(Vec::new()),
+            len: Cell::new(0),
+            chunk_size: size,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn insert(&self, data: T)->K {
+        self.insert_and_get(data).0
+    }
+
+    /// Inserts the data and returns both the key and a reference to the inserted data
+    pub fn insert_and_get(&self, data: T)->(K, &T) {
+        let id = self.len.get();
+        let (chunk, offset) = self.split_id(id);
+
+        // SAFETY: we are not `Sync`, so nothing else can be accessing the chunks right now.
+        // References handed out point into the chunks' buffers, which are never reallocated since
+        // a chunk is never pushed past its capacity.
+        let chunks = unsafe {&mut *self.chunks.get()};
+        if chunk == chunks.len() {
+            chunks.push(Vec::with_capacity(self.chunk_size));
+        }
+        let chunk = &mut chunks[chunk];
+        debug_assert!(chunk.len() < chunk.capacity(), "ChunkedKeyedVec chunk would reallocate");
+        chunk.push(data);
+        self.len.set(id + 1);
+
+        // SAFETY: the pointer comes straight from the chunk's buffer, which won't move
+        return (K::from_id(id), unsafe {&*chunk.as_ptr().add(offset)});
+    }
+
+    /// The key the next insert will return. Nothing is inserted.
+    pub fn next_key(&self)->K {
+        K::from_id(self.len.get())
+    }
+
+    pub fn get(&self, key: K)->&T {
+        let id = key.id();
+        assert!(id < self.len.get());
+
+        let (chunk, offset) = self.split_id(id);
+        return &self.chunks()[chunk][offset];
+    }
+
+    pub fn get_mut(&mut self, key: K)->&mut T {
+        let id = key.id();
+        assert!(id < self.len.get());
+
+        let (chunk, offset) = self.split_id(id);
+        return &mut self.chunks.get_mut()[chunk][offset];
+    }
+
+    /// Returns `None` if the key is out of bounds instead of panicking
+    pub fn try_get(&self, key: K)->Option<&T> {
+        let (chunk, offset) = self.split_id(key.id());
+        self.chunks().get(chunk)?.get(offset)
+    }
+
+    pub fn try_get_mut(&mut self, key: K)->Option<&mut T> {
+        let (chunk, offset) = self.split_id(key.id());
+        self.chunks.get_mut().get_mut(chunk)?.get_mut(offset)
+    }
+
+    pub fn len(&self)->usize {
+        self.len.get()
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.len.get() == 0
+    }
+
+    /// Iterate over the data in key order. Items inserted while iterating are not yielded.
+    pub fn iter<'a>(&'a self)->impl 'a + Iterator<Item = &'a T> {
+        let len = self.len.get();
+        (0..len).map(|id|self.get(K::from_id(id)))
+    }
+
+    pub fn iter_mut<'a>(&'a mut self)->impl 'a + Iterator<Item = &'a mut T> {
+        self.chunks.get_mut().iter_mut().flatten()
+    }
+
+    pub fn iter_with_keys<'a>(&'a self)->impl 'a + Iterator<Item = (K, &'a T)> {
+        self.iter().enumerate().map(|(i, t)|(K::from_id(i), t))
+    }
+
+    /// Moves all the data into one `Vec`
+    pub fn into_vec(self)->Vec<T> {
+        self.chunks.into_inner().into_iter().flatten().collect()
+    }
+}
+impl<K: Key, T> Default for ChunkedKeyedVec<K, T> {
+    fn default()->Self {
+        Self::new()
+    }
+}
+impl<K: Key, T: Debug> Debug for ChunkedKeyedVec<K, T> {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        f.debug_list()
+            .entries(self.iter())
+            .finish()
+    }
+}
+impl<K: Key, T> Index<K> for ChunkedKeyedVec<K, T> {
+    type Output = T;
+    #[inline]
+    fn index(&self, key: K)->&T {
+        self.get(key)
+    }
+}
+impl<K: Key, T> IndexMut<K> for ChunkedKeyedVec<K, T> {
+    #[inline]
+    fn index_mut(&mut self, key: K)->&mut T {
+        self.get_mut(key)
+    }
+}
+impl<K: Key, T> Extend<T> for ChunkedKeyedVec<K, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for data in iter {
+            self.insert(data);
+        }
+    }
+}
+impl<K: Key, T> FromIterator<T> for ChunkedKeyedVec<K, T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I)->Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        return vec;
+    }
+}
@@ -0,0 +1,730 @@
+use serde::{
+    Serialize,
+    Serializer,
+    Deserialize,
+    Deserializer,
+};
+use std::{
+    fmt::{
+        self,
+        Debug,
+        Formatter,
+    },
+    ops::{
+        Index,
+        IndexMut,
+    },
+    mem::MaybeUninit,
+    marker::PhantomData,
+};
+use crate::{
+    Key,
+    KeyOffset,
+    bitmap::Bitmap,
+};
+
+
+/// The same as [`crate::sparse_list::SparseList`], but stores which slots are occupied in a
+/// separate bitmask instead of wrapping each item in an `Option`. For types without a niche this
+/// saves up to a word per slot, at the cost of indexing returning the data directly (and
+/// panicking on holes) instead of an `Option`.
+///
+/// Serializes the same way as `SparseList`, so data can be moved between the two.
+pub struct CompactSparseList<K: Key, T> {
+    /// Only the slots with their bit set in `occupied` are initialized
+    inner: Vec<MaybeUninit<T>>,
+    occupied: Bitmap,
+    used_count: usize,
+    _phantom: PhantomData<K>,
+}
+impl<K: Key, T> CompactSparseList<K, T> {
+    pub fn new()->Self {
+        CompactSparseList {
+            inner: Vec::new(),
+            occupied: Bitmap::new(),
+            used_count: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Creates a new list with room for at least `capacity` slots
+    pub fn with_capacity(capacity: usize)->Self {
+        CompactSparseList {
+            inner: Vec::with_capacity(capacity),
+            occupied: Bitmap::with_capacity(capacity),
+            used_count: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Makes room for at least `additional` more slots without reallocating
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional);
+        self.occupied.reserve_bits(self.inner.len() + additional);
+    }
+
+    /// Like [`FromIterator::from_iter`], but also returns the key of each item in order
+    pub fn from_iter_with_keys<I: IntoIterator<Item = T>>(iter: I)->(Self, Vec<K>) {
+        let mut list = Self::new();
+        let keys = iter.into_iter().map(|data|list.push(data)).collect();
+
+        return (list, keys);
+    }
+
+    pub fn push(&mut self, data: T)->K {
+        let id = self.inner.len();
+        self.inner.push(MaybeUninit::new(data));
+        self.occupied.set(id);
+        self.used_count += 1;
+
+        return K::from_id(id);
+    }
+
+    /// Puts the data at exactly the given key, growing the list with holes if the key is past the
+    /// end. Returns the data that was there before, if any. No other keys are affected.
+    pub fn insert_at(&mut self, key: K, data: T)->Option<T> {
+        let id = key.id();
+        if id >= self.inner.len() {
+            self.inner.resize_with(id + 1, MaybeUninit::uninit);
+        }
+
+        let old = self.remove(key);
+        self.inner[id].write(data);
+        self.occupied.set(id);
+        self.used_count += 1;
+
+        return old;
+    }
+
+    /// Swaps the contents of two slots, holes included. No other keys are affected.
+    ///
+    /// Panics if either key is past the end of the list.
+    pub fn swap(&mut self, a: K, b: K) {
+        let len = self.inner.len();
+        assert!(
+            a.id() < len && b.id() < len,
+            "Key out of bounds for a CompactSparseList with {} slots",
+            len,
+        );
+
+        let (a, b) = (a.id(), b.id());
+        self.inner.swap(a, b);
+        let (a_set, b_set) = (self.occupied.get(a), self.occupied.get(b));
+        self.set_occupied(a, b_set);
+        self.set_occupied(b, a_set);
+    }
+
+    fn set_occupied(&mut self, id: usize, occupied: bool) {
+        if occupied {
+            self.occupied.set(id);
+        } else {
+            self.occupied.unset(id);
+        }
+    }
+
+    /// Removes the data, leaving a hole. Returns `None` if there was nothing at the key.
+    pub fn remove(&mut self, key: K)->Option<T> {
+        let id = key.id();
+        if !self.occupied.get(id) {return None}
+
+        self.occupied.unset(id);
+        self.used_count -= 1;
+
+        // SAFETY: the bit was set, so the slot is initialized. We just unset it, so it won't be
+        // read or dropped again.
+        return Some(unsafe {self.inner[id].assume_init_read()});
+    }
+
+    /// Removes the last item along with any holes after it. Returns `None` if the list has no
+    /// data, in which case all the slots are removed.
+    pub fn pop(&mut self)->Option<(K, T)> {
+        let Some(id) = self.occupied.prev_set(self.inner.len()) else {
+            self.inner.clear();
+            return None;
+        };
+
+        let data = self.remove(K::from_id(id)).unwrap();
+        self.inner.truncate(id);
+
+        return Some((K::from_id(id), data));
+    }
+
+    pub fn get(&self, key: K)->Option<&T> {
+        let id = key.id();
+        if !self.occupied.get(id) {return None}
+
+        // SAFETY: the bit is set, so the slot is initialized
+        return Some(unsafe {self.inner[id].assume_init_ref()});
+    }
+
+    pub fn get_mut(&mut self, key: K)->Option<&mut T> {
+        let id = key.id();
+        if !self.occupied.get(id) {return None}
+
+        // SAFETY: the bit is set, so the slot is initialized
+        return Some(unsafe {self.inner[id].assume_init_mut()});
+    }
+
+    pub fn contains_key(&self, key: K)->bool {
+        self.occupied.get(key.id())
+    }
+
+    /// Consumes the list and yields all the data with its key
+    pub fn into_iter_with_keys(self)->CompactSparseListIntoIterWithKeys<K, T> {
+        CompactSparseListIntoIterWithKeys {
+            front: 0,
+            back: self.inner.len(),
+            list: self,
+        }
+    }
+
+    /// Removes all the data that does not match the predicate, leaving holes in their place
+    pub fn retain<F: FnMut(K, &mut T)->bool>(&mut self, mut f: F) {
+        let mut next = self.occupied.next_set(0);
+        while let Some(id) = next {
+            // SAFETY: the bit is set, so the slot is initialized
+            if !f(K::from_id(id), unsafe {self.inner[id].assume_init_mut()}) {
+                self.remove(K::from_id(id));
+            }
+            next = self.occupied.next_set(id + 1);
+        }
+    }
+
+    /// Moves all the data to the front to fill the holes and drops the empty slots left at the end.
+    /// Returns `(old_key, new_key)` for every item that moved, in order. Keys that are not in the
+    /// list stay the same.
+    pub fn compact(&mut self)->Vec<(K, K)> {
+        let mut moved = Vec::new();
+        let mut next = 0;
+        let mut occupied = self.occupied.next_set(0);
+        while let Some(id) = occupied {
+            if id != next {
+                // `next` is a hole, so this just moves the data down
+                self.inner.swap(id, next);
+                self.occupied.unset(id);
+                self.occupied.set(next);
+                moved.push((K::from_id(id), K::from_id(next)));
+            }
+            next += 1;
+            occupied = self.occupied.next_set(id + 1);
+        }
+        self.inner.truncate(next);
+
+        return moved;
+    }
+
+    /// Moves all the slots of `other` (holes included) onto the end of this list. Returns the
+    /// offset that translates `other`'s keys into keys in this list.
+    pub fn append(&mut self, mut other: CompactSparseList<K, T>)->KeyOffset {
+        let offset = self.inner.len();
+
+        // `other` is left empty, so dropping it won't drop the data that was moved out
+        let occupied = std::mem::take(&mut other.occupied);
+        self.used_count += std::mem::take(&mut other.used_count);
+        self.inner.append(&mut other.inner);
+
+        let mut next = occupied.next_set(0);
+        while let Some(id) = next {
+            self.occupied.set(offset + id);
+            next = occupied.next_set(id + 1);
+        }
+
+        return KeyOffset(offset);
+    }
+
+    /// Removes all the data and slots, but keeps the allocation around
+    pub fn clear(&mut self) {
+        // Empty the list before dropping anything, so if a drop panics the rest of the items are
+        // leaked instead of being dropped again by the next `clear`.
+        let occupied = std::mem::take(&mut self.occupied);
+        self.used_count = 0;
+
+        let mut next = occupied.next_set(0);
+        while let Some(id) = next {
+            // SAFETY: the bit was set, so the slot is initialized. The list's own bitmap is already
+            // empty, so nothing will read or drop the slot again.
+            unsafe {self.inner[id].assume_init_drop()};
+            next = occupied.next_set(id + 1);
+        }
+
+        self.inner.clear();
+    }
+
+    /// Removes and yields all the data with its key. The list is empty afterwards, but keeps its
+    /// allocation. The data that isn't yielded is dropped along with the iterator.
+    pub fn drain<'a>(&'a mut self)->CompactSparseListDrain<'a, K, T> {
+        CompactSparseListDrain {
+            front: 0,
+            back: self.inner.len(),
+            list: self,
+        }
+    }
+
+    /// Drops trailing empty slots and releases unused capacity. Never touches occupied slots, so
+    /// [`Self::used_count`] stays the same. Returns `true` if the backing store actually shrank.
+    pub fn shrink_to_fit(&mut self)->bool {
+        let old_len = self.inner.len();
+        let old_capacity = self.inner.capacity();
+
+        let len = self.occupied.prev_set(old_len).map(|id|id + 1).unwrap_or(0);
+        // the slots past `len` are all holes, so there is nothing to drop
+        self.inner.truncate(len);
+        self.inner.shrink_to_fit();
+        self.occupied.shrink_to_fit();
+
+        return self.inner.len() != old_len || self.inner.capacity() != old_capacity;
+    }
+
+    /// If there is no data in the list. There may still be empty slots.
+    pub fn is_empty(&self)->bool {
+        self.used_count == 0
+    }
+
+    pub fn used_count(&self)->usize {
+        self.used_count
+    }
+
+    pub fn slot_count(&self)->usize {
+        self.inner.len()
+    }
+
+    pub fn iter<'a>(&'a self)->impl 'a + DoubleEndedIterator<Item = &'a T> + ExactSizeIterator {
+        self.iter_with_keys().map(|(_, t)|t)
+    }
+
+    pub fn iter_mut<'a>(&'a mut self)
+        ->impl 'a + DoubleEndedIterator<Item = &'a mut T> + ExactSizeIterator
+    {
+        self.iter_mut_with_keys().map(|(_, t)|t)
+    }
+
+    pub fn iter_with_keys<'a>(&'a self)->CompactSparseListIterWithKeys<'a, K, T> {
+        CompactSparseListIterWithKeys {
+            inner: &self.inner,
+            occupied: &self.occupied,
+            front: 0,
+            back: self.inner.len(),
+            remaining: self.used_count,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn iter_mut_with_keys<'a>(&'a mut self)->CompactSparseListIterMutWithKeys<'a, K, T> {
+        CompactSparseListIterMutWithKeys {
+            inner: self.inner.as_mut_ptr(),
+            occupied: &self.occupied,
+            front: 0,
+            back: self.inner.len(),
+            remaining: self.used_count,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn iter_keys<'a>(&'a self)->impl 'a + DoubleEndedIterator<Item = K> + ExactSizeIterator {
+        self.iter_with_keys().map(|(k, _)|k)
+    }
+
+    /// Iterate over every slot, including the holes
+    pub fn iter_slots<'a>(&'a self)
+        ->impl 'a + DoubleEndedIterator<Item = (K, Option<&'a T>)> + ExactSizeIterator
+    {
+        (0..self.inner.len()).map(|id|(K::from_id(id), self.get(K::from_id(id))))
+    }
+
+    /// Iterate over the keys of the holes
+    pub fn holes<'a>(&'a self)->CompactSparseListHoles<'a, K> {
+        CompactSparseListHoles {
+            occupied: &self.occupied,
+            front: 0,
+            back: self.inner.len(),
+            remaining: self.inner.len() - self.used_count,
+            _phantom: PhantomData,
+        }
+    }
+}
+impl<K: Key, T> Drop for CompactSparseList<K, T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+impl<K: Key, T: Clone> Clone for CompactSparseList<K, T> {
+    fn clone(&self)->Self {
+        let inner = (0..self.inner.len())
+            .map(|id|match self.get(K::from_id(id)) {
+                Some(t)=>MaybeUninit::new(t.clone()),
+                None=>MaybeUninit::uninit(),
+            })
+            .collect();
+
+        CompactSparseList {
+            inner,
+            occupied: self.occupied.clone(),
+            used_count: self.used_count,
+            _phantom: PhantomData,
+        }
+    }
+}
+impl<K: Key, T> Default for CompactSparseList<K, T> {
+    fn default()->Self {
+        Self::new()
+    }
+}
+/// Shows every slot, with `None` for the holes
+impl<K: Key, T: Debug> Debug for CompactSparseList<K, T> {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        f.debug_list()
+            .entries((0..self.inner.len()).map(|id|self.get(K::from_id(id))))
+            .finish()
+    }
+}
+impl<K: Key, T> Index<K> for CompactSparseList<K, T> {
+    type Output = T;
+    fn index(&self, key: K)->&T {
+        self.get(key).expect("No data at the key")
+    }
+}
+impl<K: Key, T> IndexMut<K> for CompactSparseList<K, T> {
+    fn index_mut(&mut self, key: K)->&mut T {
+        self.get_mut(key).expect("No data at the key")
+    }
+}
+/// Serializes as the list of slots, with `None` for holes
+impl<K: Key, T: Serialize> Serialize for CompactSparseList<K, T> {
+    fn serialize<S: Serializer>(&self, serializer: S)->Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter_slots().map(|(_, t)|t))
+    }
+}
+impl<'de, K: Key, T: Deserialize<'de>> Deserialize<'de> for CompactSparseList<K, T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)->Result<Self, D::Error> {
+        let slots = Vec::<Option<T>>::deserialize(deserializer)?;
+
+        let mut list = Self::with_capacity(slots.len());
+        for slot in slots {
+            match slot {
+                Some(data)=>{list.push(data);},
+                None=>list.inner.push(MaybeUninit::uninit()),
+            }
+        }
+
+        return Ok(list);
+    }
+}
+impl<K: Key, T> IntoIterator for CompactSparseList<K, T> {
+    type Item = T;
+    type IntoIter = CompactSparseListIntoIter<K, T>;
+    fn into_iter(self)->Self::IntoIter {
+        CompactSparseListIntoIter {
+            inner: self.into_iter_with_keys(),
+        }
+    }
+}
+impl<K: Key, T> Extend<T> for CompactSparseList<K, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for data in iter {
+            self.push(data);
+        }
+    }
+}
+impl<K: Key, T> FromIterator<T> for CompactSparseList<K, T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I)->Self {
+        let mut list = Self::new();
+        list.extend(iter);
+        return list;
+    }
+}
+
+/// Yields the occupied slots in `front..back`, finding them with the bitmap
+pub struct CompactSparseListIterWithKeys<'a, K: Key, T: 'a> {
+    inner: &'a [MaybeUninit<T>],
+    occupied: &'a Bitmap,
+    front: usize,
+    back: usize,
+    remaining: usize,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, T: 'a> Iterator for CompactSparseListIterWithKeys<'a, K, T> {
+    type Item = (K, &'a T);
+    fn next(&mut self)->Option<Self::Item> {
+        let id = self.occupied.next_set(self.front).filter(|id|*id < self.back)?;
+        self.front = id + 1;
+        self.remaining -= 1;
+
+        // SAFETY: the bit is set, so the slot is initialized
+        return Some((K::from_id(id), unsafe {self.inner[id].assume_init_ref()}));
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<'a, K: Key, T: 'a> DoubleEndedIterator for CompactSparseListIterWithKeys<'a, K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        let id = self.occupied.prev_set(self.back).filter(|id|*id >= self.front)?;
+        self.back = id;
+        self.remaining -= 1;
+
+        // SAFETY: the bit is set, so the slot is initialized
+        return Some((K::from_id(id), unsafe {self.inner[id].assume_init_ref()}));
+    }
+}
+impl<'a, K: Key, T: 'a> ExactSizeIterator for CompactSparseListIterWithKeys<'a, K, T> {}
+
+/// Same as [`CompactSparseListIterWithKeys`], but holds a raw pointer so it can hand out mutable
+/// references to different slots from both ends.
+pub struct CompactSparseListIterMutWithKeys<'a, K: Key, T: 'a> {
+    inner: *mut MaybeUninit<T>,
+    occupied: &'a Bitmap,
+    front: usize,
+    back: usize,
+    remaining: usize,
+    _phantom: PhantomData<(K, &'a mut T)>,
+}
+impl<'a, K: Key, T: 'a> Iterator for CompactSparseListIterMutWithKeys<'a, K, T> {
+    type Item = (K, &'a mut T);
+    fn next(&mut self)->Option<Self::Item> {
+        let id = self.occupied.next_set(self.front).filter(|id|*id < self.back)?;
+        self.front = id + 1;
+        self.remaining -= 1;
+
+        // SAFETY: `id < back <= len` and the bit is set, so the slot is in bounds and initialized.
+        // `front` moved past it, so it is never handed out twice.
+        return Some((K::from_id(id), unsafe {(*self.inner.add(id)).assume_init_mut()}));
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<'a, K: Key, T: 'a> DoubleEndedIterator for CompactSparseListIterMutWithKeys<'a, K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        let id = self.occupied.prev_set(self.back).filter(|id|*id >= self.front)?;
+        self.back = id;
+        self.remaining -= 1;
+
+        // SAFETY: same as `next`, with `back` moved below the slot instead
+        return Some((K::from_id(id), unsafe {(*self.inner.add(id)).assume_init_mut()}));
+    }
+}
+impl<'a, K: Key, T: 'a> ExactSizeIterator for CompactSparseListIterMutWithKeys<'a, K, T> {}
+
+
+/// Consumes a [`CompactSparseList`], removing the data as it is yielded. Whatever is left is
+/// dropped with the list.
+pub struct CompactSparseListIntoIterWithKeys<K: Key, T> {
+    list: CompactSparseList<K, T>,
+    front: usize,
+    back: usize,
+}
+impl<K: Key, T> Iterator for CompactSparseListIntoIterWithKeys<K, T> {
+    type Item = (K, T);
+    fn next(&mut self)->Option<Self::Item> {
+        let id = self.list.occupied.next_set(self.front).filter(|id|*id < self.back)?;
+        self.front = id + 1;
+
+        return Some((K::from_id(id), self.list.remove(K::from_id(id))?));
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.list.used_count, Some(self.list.used_count))
+    }
+}
+impl<K: Key, T> DoubleEndedIterator for CompactSparseListIntoIterWithKeys<K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        let id = self.list.occupied.prev_set(self.back).filter(|id|*id >= self.front)?;
+        self.back = id;
+
+        return Some((K::from_id(id), self.list.remove(K::from_id(id))?));
+    }
+}
+impl<K: Key, T> ExactSizeIterator for CompactSparseListIntoIterWithKeys<K, T> {}
+
+pub struct CompactSparseListIntoIter<K: Key, T> {
+    inner: CompactSparseListIntoIterWithKeys<K, T>,
+}
+impl<K: Key, T> Iterator for CompactSparseListIntoIter<K, T> {
+    type Item = T;
+    fn next(&mut self)->Option<Self::Item> {
+        self.inner.next().map(|(_, t)|t)
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<K: Key, T> DoubleEndedIterator for CompactSparseListIntoIter<K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        self.inner.next_back().map(|(_, t)|t)
+    }
+}
+impl<K: Key, T> ExactSizeIterator for CompactSparseListIntoIter<K, T> {}
+
+/// Same as [`CompactSparseListIntoIterWithKeys`], but only borrows the list and clears it when
+/// dropped
+pub struct CompactSparseListDrain<'a, K: Key, T: 'a> {
+    list: &'a mut CompactSparseList<K, T>,
+    front: usize,
+    back: usize,
+}
+impl<'a, K: Key, T: 'a> Iterator for CompactSparseListDrain<'a, K, T> {
+    type Item = (K, T);
+    fn next(&mut self)->Option<Self::Item> {
+        let id = self.list.occupied.next_set(self.front).filter(|id|*id < self.back)?;
+        self.front = id + 1;
+
+        return Some((K::from_id(id), self.list.remove(K::from_id(id))?));
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.list.used_count, Some(self.list.used_count))
+    }
+}
+impl<'a, K: Key, T: 'a> DoubleEndedIterator for CompactSparseListDrain<'a, K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        let id = self.list.occupied.prev_set(self.back).filter(|id|*id >= self.front)?;
+        self.back = id;
+
+        return Some((K::from_id(id), self.list.remove(K::from_id(id))?));
+    }
+}
+impl<'a, K: Key, T: 'a> ExactSizeIterator for CompactSparseListDrain<'a, K, T> {}
+impl<'a, K: Key, T: 'a> Drop for CompactSparseListDrain<'a, K, T> {
+    fn drop(&mut self) {
+        self.list.clear();
+    }
+}
+
+pub struct CompactSparseListHoles<'a, K: Key> {
+    occupied: &'a Bitmap,
+    front: usize,
+    back: usize,
+    remaining: usize,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key> Iterator for CompactSparseListHoles<'a, K> {
+    type Item = K;
+    fn next(&mut self)->Option<Self::Item> {
+        while self.front < self.back {
+            let id = self.front;
+            self.front += 1;
+            if !self.occupied.get(id) {
+                self.remaining -= 1;
+                return Some(K::from_id(id));
+            }
+        }
+
+        return None;
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<'a, K: Key> DoubleEndedIterator for CompactSparseListHoles<'a, K> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        while self.front < self.back {
+            self.back -= 1;
+            if !self.occupied.get(self.back) {
+                self.remaining -= 1;
+                return Some(K::from_id(self.back));
+            }
+        }
+
+        return None;
+    }
+}
+impl<'a, K: Key> ExactSizeIterator for CompactSparseListHoles<'a, K> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        panic,
+        sync::atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+    };
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct PanicOnDrop(bool);
+    impl Drop for PanicOnDrop {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::SeqCst);
+            if self.0 {
+                panic!("drop panicked");
+            }
+        }
+    }
+
+    #[test]
+    fn matches_sparse_list() {
+        use crate::SparseList;
+
+        let mut compact = CompactSparseList::<usize, i32>::from_iter([1, 2, 3, 4]);
+        let mut sparse = SparseList::<usize, i32>::from_iter([1, 2, 3, 4]);
+        compact.remove(1);
+        sparse.remove(1);
+        compact.insert_at(6, 7);
+        sparse.insert_at(6, 7);
+        compact.swap(0, 5);
+        sparse.swap(0, 5);
+        compact.retain(|_, t|*t != 3);
+        sparse.retain(|_, t|*t != 3);
+
+        assert_eq!(compact.holes().collect::<Vec<_>>(), sparse.holes().collect::<Vec<_>>());
+        assert_eq!(compact.holes().len(), sparse.holes().len());
+        assert_eq!(
+            compact.iter_slots().collect::<Vec<_>>(),
+            sparse.iter_slots().collect::<Vec<_>>(),
+        );
+        assert_eq!(compact.compact(), sparse.compact());
+        assert_eq!(compact.iter().collect::<Vec<_>>(), sparse.iter().collect::<Vec<_>>());
+
+        let offset = compact.append(CompactSparseList::from_iter([8]));
+        assert_eq!(offset, sparse.append(SparseList::from_iter([8])));
+        assert_eq!(compact.get(offset.translate(0)), Some(&8));
+
+        compact.remove(3);
+        sparse.remove(3);
+        assert_eq!(compact.shrink_to_fit(), sparse.shrink_to_fit());
+        assert_eq!(compact.slot_count(), sparse.slot_count());
+        assert_eq!(
+            compact.drain().rev().collect::<Vec<_>>(),
+            sparse.drain().rev().collect::<Vec<_>>(),
+        );
+        assert!(compact.is_empty());
+    }
+
+    #[test]
+    fn unfinished_iterators_drop_the_rest() {
+        let item = std::rc::Rc::new(());
+        let mut list = CompactSparseList::<usize, _>::from_iter([item.clone(), item.clone()]);
+        list.push(item.clone());
+        list.push(item.clone());
+
+        list.drain().next();
+        assert!(list.is_empty());
+        assert_eq!(std::rc::Rc::strong_count(&item), 1);
+
+        list.extend([item.clone(), item.clone()]);
+        list.into_iter().next_back();
+        assert_eq!(std::rc::Rc::strong_count(&item), 1);
+    }
+
+    #[test]
+    fn panicking_drop_does_not_double_drop() {
+        let mut list = CompactSparseList::<usize, PanicOnDrop>::new();
+        list.push(PanicOnDrop(false));
+        list.push(PanicOnDrop(true));
+        list.push(PanicOnDrop(false));
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(||list.clear()));
+        assert!(result.is_err());
+        assert!(list.is_empty());
+
+        // the first two were dropped, and the last one is leaked instead of dropped later
+        drop(list);
+        assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    sync::{
+        RwLock,
+        RwLockReadGuard,
+        RwLockWriteGuard,
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+    },
+    marker::PhantomData,
+};
+use crate::{
+    Key,
+    slotmap::SlotMap,
+};
+
+
+const DEFAULT_SHARD_COUNT: usize = 16;
+
+
+/// A [`SlotMap`] that can be shared between threads. The slots are split into shards that each
+/// have their own lock, and inserts are spread across the shards so threads rarely wait on each
+/// other. The shard is encoded in the key, so keys can be sent to any thread. Like `SlotMap`, this
+/// DOES NOT solve the ABA problem.
+///
+/// Since the data lives behind a lock, shared access goes through closures (`with`, `with_mut`)
+/// instead of returning references. Iteration requires exclusive access to the whole map.
+#[derive(Debug)]
+pub struct ConcurrentSlotMap<K: Key, T> {
+    shards: Box<[RwLock<SlotMap<usize, T>>]>,
+    next_shard: AtomicUsize,
+    _phantom: PhantomData<fn()->K>,
+}
+impl<K: Key, T> ConcurrentSlotMap<K, T> {
+    /// Splits the key into the shard index and the key within that shard
+    #[inline]
+    fn split_key(&self, key: &K)->(usize, usize) {
+        let id = key.id();
+        (id % self.shards.len(), id / self.shards.len())
+    }
+
+    #[inline]
+    fn join_key(&self, shard: usize, local: usize)->K {
+        K::from_id(local * self.shards.len() + shard)
+    }
+
+    fn read(&self, shard: usize)->RwLockReadGuard<'_, SlotMap<usize, T>> {
+        self.shards[shard].read().expect("ConcurrentSlotMap shard lock is poisoned")
+    }
+
+    fn write(&self, shard: usize)->RwLockWriteGuard<'_, SlotMap<usize, T>> {
+        self.shards[shard].write().expect("ConcurrentSlotMap shard lock is poisoned")
+    }
+
+    pub fn new()->Self {
+        Self::with_shard_count(DEFAULT_SHARD_COUNT)
+    }
+
+    /// Creates a map with `count` shards. More shards means less contention, but keys grow faster.
+    ///
+    /// Panics if `count` is zero.
+    pub fn with_shard_count(count: usize)->Self {
+        assert!(count > 0, "ConcurrentSlotMap needs at least one shard");
+
+        ConcurrentSlotMap {
+            shards: (0..count).map(|_|RwLock::new(SlotMap::new())).collect(),
+            next_shard: AtomicUsize::new(0),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn insert(&self, data: T)->K {
+        let shard = self.next_shard.fetch_add(1, Ordering::Relaxed) % self.shards.len();
+        let local = self.write(shard).insert(data);
+
+        return self.join_key(shard, local);
+    }
+
+    pub fn contains_key(&self, key: K)->bool {
+        let (shard, local) = self.split_key(&key);
+        return self.read(shard).contains_key(local);
+    }
+
+    /// Calls `f` with a reference to the data while holding the shard's read lock
+    pub fn with<R, F: FnOnce(&T)->R>(&self, key: K, f: F)->Option<R> {
+        let (shard, local) = self.split_key(&key);
+        return self.read(shard).get(local).map(f);
+    }
+
+    /// Calls `f` with a mutable reference to the data while holding the shard's write lock
+    pub fn with_mut<R, F: FnOnce(&mut T)->R>(&self, key: K, f: F)->Option<R> {
+        let (shard, local) = self.split_key(&key);
+        return self.write(shard).get_mut(local).map(f);
+    }
+
+    /// Clones the data out of the map
+    pub fn get_cloned(&self, key: K)->Option<T> where T: Clone {
+        self.with(key, T::clone)
+    }
+
+    pub fn remove(&self, key: K)->Option<T> {
+        let (shard, local) = self.split_key(&key);
+        return self.write(shard).remove(local);
+    }
+
+    /// The number of occupied slots. Other threads may change this at any time.
+    pub fn len(&self)->usize {
+        (0..self.shards.len()).map(|shard|self.read(shard).len()).sum()
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.len() == 0
+    }
+
+    /// Direct access to the data without locking, since we have exclusive access
+    pub fn get_mut(&mut self, key: K)->Option<&mut T> {
+        let (shard, local) = self.split_key(&key);
+        return self.shards[shard]
+            .get_mut()
+            .expect("ConcurrentSlotMap shard lock is poisoned")
+            .get_mut(local);
+    }
+
+    /// Iterate over all `(key, value)` pairs mutably, shard by shard
+    pub fn iter_mut<'a>(&'a mut self)->impl 'a + Iterator<Item = (K, &'a mut T)> {
+        let shard_count = self.shards.len();
+        self.shards
+            .iter_mut()
+            .enumerate()
+            .flat_map(move |(shard, lock)|{
+                lock.get_mut()
+                    .expect("ConcurrentSlotMap shard lock is poisoned")
+                    .iter_mut()
+                    .map(move |(local, t)|(K::from_id(local * shard_count + shard), t))
+            })
+    }
+}
@@ -0,0 +1,161 @@
+use serde::{Serialize, Deserialize};
+use std::{
+    ops::{
+        Index,
+        IndexMut,
+    },
+    slice::{
+        Iter,
+        IterMut,
+    },
+    iter::Zip,
+};
+use crate::Key;
+
+
+/// A [`crate::slotmap::SlotMap`] that keeps all of its data packed together in one `Vec`, so
+/// iteration never touches empty slots. Lookups go through an extra indirection table, and removal
+/// moves the last value into the hole, so the order of the values is not stable. Like `SlotMap`,
+/// this DOES NOT solve the ABA problem.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DenseSlotMap<K: Key, T> {
+    values: Vec<T>,
+    /// The key of each value in `values`
+    keys: Vec<K>,
+    /// The index into `values` for each key
+    slots: Vec<Option<usize>>,
+    free: Vec<K>,
+}
+impl<K: Key, T> DenseSlotMap<K, T> {
+    pub fn new()->Self {
+        DenseSlotMap {
+            values: Vec::new(),
+            keys: Vec::new(),
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, data: T)->K {
+        let index = self.values.len();
+        let key;
+        if let Some(k) = self.free.pop() {
+            debug_assert!(self.slots[k.id()].is_none(), "DenseSlotMap free list points at an occupied slot");
+            key = k;
+            self.slots[key.id()] = Some(index);
+        } else {
+            key = K::from_id(self.slots.len());
+            self.slots.push(Some(index));
+        }
+
+        self.values.push(data);
+        self.keys.push(K::from_id(key.id()));
+
+        return key;
+    }
+
+    pub fn get(&self, key: K)->Option<&T> {
+        let index = (*self.slots.get(key.id())?)?;
+        return Some(&self.values[index]);
+    }
+
+    pub fn get_mut(&mut self, key: K)->Option<&mut T> {
+        let index = (*self.slots.get(key.id())?)?;
+        return Some(&mut self.values[index]);
+    }
+
+    pub fn contains_key(&self, key: K)->bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes the data and frees the key. The last value is moved into the hole left behind.
+    pub fn remove(&mut self, key: K)->Option<T> {
+        let id = key.id();
+        let index = self.slots.get_mut(id)?.take()?;
+
+        let data = self.values.swap_remove(index);
+        self.keys.swap_remove(index);
+        if index < self.keys.len() {
+            self.slots[self.keys[index].id()] = Some(index);
+        }
+        self.free.push(key);
+
+        return Some(data);
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.keys.clear();
+        self.slots.clear();
+        self.free.clear();
+    }
+
+    /// The number of values stored
+    pub fn len(&self)->usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.values.is_empty()
+    }
+
+    /// Iterate over all `(key, value)` pairs in storage order
+    pub fn iter<'a>(&'a self)->DenseSlotMapIter<'a, K, T> {
+        DenseSlotMapIter(self.keys.iter().zip(self.values.iter()))
+    }
+
+    /// Iterate over all `(key, value)` pairs mutably in storage order
+    pub fn iter_mut<'a>(&'a mut self)->DenseSlotMapIterMut<'a, K, T> {
+        DenseSlotMapIterMut(self.keys.iter().zip(self.values.iter_mut()))
+    }
+
+    pub fn keys<'a>(&'a self)->impl 'a + Iterator<Item = K> {
+        self.keys.iter().map(|k|K::from_id(k.id()))
+    }
+
+    pub fn values<'a>(&'a self)->Iter<'a, T> {
+        self.values.iter()
+    }
+
+    pub fn values_mut<'a>(&'a mut self)->IterMut<'a, T> {
+        self.values.iter_mut()
+    }
+
+    /// All the values as one contiguous slice
+    pub fn as_slice(&self)->&[T] {
+        &self.values
+    }
+
+    pub fn as_mut_slice(&mut self)->&mut [T] {
+        &mut self.values
+    }
+}
+impl<K: Key, T> Index<K> for DenseSlotMap<K, T> {
+    type Output = T;
+    #[inline]
+    fn index(&self, key: K)->&T {
+        self.get(key).unwrap()
+    }
+}
+impl<K: Key, T> IndexMut<K> for DenseSlotMap<K, T> {
+    #[inline]
+    fn index_mut(&mut self, key: K)->&mut T {
+        self.get_mut(key).unwrap()
+    }
+}
+
+pub struct DenseSlotMapIter<'a, K: Key, T: 'a>(Zip<Iter<'a, K>, Iter<'a, T>>);
+impl<'a, K: Key, T: 'a> Iterator for DenseSlotMapIter<'a, K, T> {
+    type Item = (K, &'a T);
+    fn next(&mut self)->Option<Self::Item> {
+        self.0.next().map(|(k, t)|(K::from_id(k.id()), t))
+    }
+}
+
+pub struct DenseSlotMapIterMut<'a, K: Key, T: 'a>(Zip<Iter<'a, K>, IterMut<'a, T>>);
+impl<'a, K: Key, T: 'a> Iterator for DenseSlotMapIterMut<'a, K, T> {
+    type Item = (K, &'a mut T);
+    fn next(&mut self)->Option<Self::Item> {
+        self.0.next().map(|(k, t)|(K::from_id(k.id()), t))
+    }
+}
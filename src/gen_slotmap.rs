@@ -0,0 +1,133 @@
+use serde::{Serialize, Deserialize};
+use std::ops::{
+    Index,
+    IndexMut,
+};
+use crate::Key;
+
+
+/// A key into a [`GenSlotMap`]. Pairs the slot key with the generation of the slot at the time
+/// of insertion.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct GenKey<K: Key> {
+    pub key: K,
+    pub generation: u32,
+}
+
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct GenSlot<T> {
+    generation: u32,
+    data: Option<T>,
+}
+
+
+/// Like [`crate::slotmap::SlotMap`], but each slot has a generation counter that is bumped when
+/// the data is removed. Keys carry the generation they were created with, so stale keys return
+/// `None` instead of silently pointing at whatever reused the slot. This solves the ABA problem
+/// at the cost of a `u32` per slot and per key.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GenSlotMap<K: Key, T> {
+    inner: Vec<GenSlot<T>>,
+    free: Vec<K>,
+    len: usize,
+}
+impl<K: Key, T> GenSlotMap<K, T> {
+    fn slot(&self, key: &GenKey<K>)->Option<&GenSlot<T>> {
+        let slot = self.inner.get(key.key.id())?;
+        if slot.generation != key.generation {return None}
+
+        return Some(slot);
+    }
+
+    fn slot_mut(&mut self, key: &GenKey<K>)->Option<&mut GenSlot<T>> {
+        let slot = self.inner.get_mut(key.key.id())?;
+        if slot.generation != key.generation {return None}
+
+        return Some(slot);
+    }
+
+    pub fn new()->Self {
+        GenSlotMap {
+            inner: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, data: T)->GenKey<K> {
+        self.len += 1;
+
+        if let Some(key) = self.free.pop() {
+            let slot = &mut self.inner[key.id()];
+            debug_assert!(slot.data.is_none(), "GenSlotMap free list points at an occupied slot");
+            slot.data = Some(data);
+
+            return GenKey {
+                key,
+                generation: slot.generation,
+            };
+        }
+
+        let key = K::from_id(self.inner.len());
+        self.inner.push(GenSlot {
+            generation: 0,
+            data: Some(data),
+        });
+
+        return GenKey {
+            key,
+            generation: 0,
+        };
+    }
+
+    pub fn get(&self, key: GenKey<K>)->Option<&T> {
+        self.slot(&key)?.data.as_ref()
+    }
+
+    pub fn get_mut(&mut self, key: GenKey<K>)->Option<&mut T> {
+        self.slot_mut(&key)?.data.as_mut()
+    }
+
+    pub fn contains_key(&self, key: GenKey<K>)->bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes the data and bumps the slot's generation, invalidating every key to it.
+    pub fn remove(&mut self, key: GenKey<K>)->Option<T> {
+        let slot = self.slot_mut(&key)?;
+        let data = slot.data.take()?;
+
+        // a slot that has been through every generation is retired instead of wrapping around
+        // and aliasing the very first key.
+        slot.generation = slot.generation.wrapping_add(1);
+        if slot.generation != 0 {
+            self.free.push(key.key);
+        }
+        self.len -= 1;
+
+        return Some(data);
+    }
+
+    /// The number of occupied slots
+    pub fn len(&self)->usize {
+        self.len
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.len == 0
+    }
+}
+impl<K: Key, T> Index<GenKey<K>> for GenSlotMap<K, T> {
+    type Output = T;
+    #[inline]
+    fn index(&self, key: GenKey<K>)->&T {
+        self.get(key).unwrap()
+    }
+}
+impl<K: Key, T> IndexMut<GenKey<K>> for GenSlotMap<K, T> {
+    #[inline]
+    fn index_mut(&mut self, key: GenKey<K>)->&mut T {
+        self.get_mut(key).unwrap()
+    }
+}
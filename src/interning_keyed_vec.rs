@@ -0,0 +1,125 @@
+use std::{
+    collections::HashMap,
+    hash::{
+        BuildHasher,
+        Hash,
+        RandomState,
+    },
+    ops::Index,
+    slice::Iter,
+};
+use crate::{
+    Key,
+    keyed_vec::KeyedVec,
+};
+
+
+/// A [`KeyedVec`] that only stores one copy of equal values, like a constant pool. Inserting a value
+/// that is already in the vec returns the existing key. Mutable access is not possible, since
+/// changing a value would break the lookup.
+#[derive(Debug, Clone)]
+pub struct InterningKeyedVec<K: Key, T: Hash + Eq> {
+    inner: KeyedVec<K, T>,
+    /// The ids of the values with each hash. The values are hashed instead of being stored as the
+    /// map's keys so we don't need a second copy of each of them.
+    index: HashMap<u64, Vec<usize>>,
+    hasher: RandomState,
+}
+impl<K: Key, T: Hash + Eq> InterningKeyedVec<K, T> {
+    pub fn new()->Self {
+        InterningKeyedVec {
+            inner: KeyedVec::new(),
+            index: HashMap::new(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Returns the key of the equal value if there is one, otherwise inserts the value
+    pub fn insert(&mut self, data: T)->K {
+        let hash = self.hasher.hash_one(&data);
+        let ids = self.index.entry(hash).or_default();
+        for id in ids.iter() {
+            if self.inner.as_slice()[*id] == data {
+                return K::from_id(*id);
+            }
+        }
+
+        let key = self.inner.insert(data);
+        ids.push(key.id());
+
+        return key;
+    }
+
+    /// The key of the value equal to `data`, if there is one
+    pub fn get_key(&self, data: &T)->Option<K> {
+        let hash = self.hasher.hash_one(data);
+        return self.index.get(&hash)?
+            .iter()
+            .find(|id|self.inner.as_slice()[**id] == *data)
+            .map(|id|K::from_id(*id));
+    }
+
+    pub fn contains(&self, data: &T)->bool {
+        self.get_key(data).is_some()
+    }
+
+    pub fn get(&self, key: K)->&T {
+        self.inner.get(key)
+    }
+
+    /// Returns `None` if the key is out of bounds instead of panicking
+    pub fn try_get(&self, key: K)->Option<&T> {
+        self.inner.try_get(key)
+    }
+
+    pub fn len(&self)->usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.inner.is_empty()
+    }
+
+    pub fn as_slice(&self)->&[T] {
+        self.inner.as_slice()
+    }
+
+    pub fn iter<'a>(&'a self)->Iter<'a, T> {
+        self.inner.iter()
+    }
+
+    pub fn iter_with_keys<'a>(&'a self)->impl 'a + DoubleEndedIterator<Item = (K, &'a T)> + ExactSizeIterator {
+        self.inner.iter_with_keys()
+    }
+
+    /// Drops the lookup and returns the plain vec. The keys stay the same.
+    pub fn into_inner(self)->KeyedVec<K, T> {
+        self.inner
+    }
+}
+impl<K: Key, T: Hash + Eq> Default for InterningKeyedVec<K, T> {
+    fn default()->Self {
+        Self::new()
+    }
+}
+impl<K: Key, T: Hash + Eq> Index<K> for InterningKeyedVec<K, T> {
+    type Output = T;
+    #[inline]
+    fn index(&self, key: K)->&T {
+        self.get(key)
+    }
+}
+impl<K: Key, T: Hash + Eq> Extend<T> for InterningKeyedVec<K, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for data in iter {
+            self.insert(data);
+        }
+    }
+}
+impl<K: Key, T: Hash + Eq> FromIterator<T> for InterningKeyedVec<K, T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I)->Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        return vec;
+    }
+}
@@ -3,15 +3,37 @@ use std::{
     ops::{
         Index,
         IndexMut,
+        Range,
+    },
+    slice::{
+        Iter,
+        IterMut,
+    },
+    iter::{
+        Enumerate,
+        IntoIterator,
+        FromIterator,
+        Extend,
     },
     marker::PhantomData,
+    cmp::Ordering,
+    hash::{
+        Hash,
+        Hasher,
+    },
+};
+use crate::{
+    Key,
+    KeyRange,
+    KeyOffset,
+    Remap,
 };
-use crate::Key;
 
 
-/// A simple keyed list of data. Removal is not possible. Basically a `Vec<T>`, but avoids the
-/// hassle of using raw `usize` to index a `Vec` and makes it a type error if the key comes from
-/// another kind of map.
+/// A simple keyed list of data. Basically a `Vec<T>`, but avoids the hassle of using raw `usize`
+/// to index a `Vec` and makes it a type error if the key comes from another kind of map. Data can
+/// only be removed from the end with [`Self::pop`] and [`Self::truncate_to`], which invalidate the
+/// keys of the removed data.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct KeyedVec<K: Key, T> {
     inner: Vec<T>,
@@ -25,12 +47,90 @@ impl<K: Key, T> KeyedVec<K, T> {
         }
     }
 
+    /// Wraps an existing `Vec`. The key of each item is its index.
+    pub fn from_vec(inner: Vec<T>)->Self {
+        KeyedVec {
+            inner,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Creates a new vec with room for at least `capacity` items
+    pub fn with_capacity(capacity: usize)->Self {
+        KeyedVec {
+            inner: Vec::with_capacity(capacity),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Makes room for at least `additional` more items without reallocating
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional);
+    }
+
+    pub fn capacity(&self)->usize {
+        self.inner.capacity()
+    }
+
     pub fn insert(&mut self, data: T)->K {
         let key = K::from_id(self.inner.len());
         self.inner.push(data);
         return key;
     }
 
+    /// Inserts the value returned by `f`, which is given the key the value will have
+    pub fn insert_with<F: FnOnce(K)->T>(&mut self, f: F)->K {
+        let id = self.inner.len();
+        self.inner.push(f(K::from_id(id)));
+        return K::from_id(id);
+    }
+
+    /// The key the next insert will return. Nothing is inserted.
+    pub fn next_key(&self)->K {
+        K::from_id(self.inner.len())
+    }
+
+    /// Inserts the data and returns both the key and a reference to the inserted data
+    pub fn insert_and_get(&mut self, data: T)->(K, &mut T) {
+        let key = self.insert(data);
+        return (key, self.inner.last_mut().unwrap());
+    }
+
+    /// Inserts all the data and returns the range of keys it was given
+    pub fn extend_and_keys<I: IntoIterator<Item = T>>(&mut self, iter: I)->KeyRange<K> {
+        let start = self.inner.len();
+        self.inner.extend(iter);
+
+        return KeyRange::new(K::from_id(start), K::from_id(self.inner.len()));
+    }
+
+    /// Moves all the data of `other` onto the end of this vec. The returned offset converts
+    /// `other`'s keys into keys for this vec.
+    pub fn append(&mut self, mut other: KeyedVec<K, T>)->KeyOffset {
+        let offset = self.inner.len();
+        self.inner.append(&mut other.inner);
+
+        return KeyOffset(offset);
+    }
+
+    /// Marks the current end of the vec so it can be rolled back with [`Self::truncate_to`]
+    pub fn checkpoint(&self)->K {
+        self.next_key()
+    }
+
+    /// Removes all the data inserted since the checkpoint was taken. Keys issued after the
+    /// checkpoint are invalid afterwards and will be handed out again by later inserts.
+    pub fn truncate_to(&mut self, checkpoint: K) {
+        self.inner.truncate(checkpoint.id());
+    }
+
+    /// Removes the last item. Its key is invalid afterwards and will be handed out again by the
+    /// next insert.
+    pub fn pop(&mut self)->Option<(K, T)> {
+        let data = self.inner.pop()?;
+        return Some((K::from_id(self.inner.len()), data));
+    }
+
     pub fn get(&self, key: K)->&T {
         let id = key.id();
         assert!(id < self.inner.len());
@@ -43,9 +143,160 @@ impl<K: Key, T> KeyedVec<K, T> {
         return &mut self.inner[id];
     }
 
+    /// Returns `None` if the key is out of bounds instead of panicking
+    pub fn try_get(&self, key: K)->Option<&T> {
+        self.inner.get(key.id())
+    }
+
+    pub fn try_get_mut(&mut self, key: K)->Option<&mut T> {
+        self.inner.get_mut(key.id())
+    }
+
+    /// Get mutable references to the data of several keys at once. Returns `None` if any of the
+    /// keys are equal or out of bounds.
+    pub fn get_many_mut<const N: usize>(&mut self, keys: [K; N])->Option<[&mut T; N]> {
+        self.inner.get_disjoint_mut(keys.map(|k|k.id())).ok()
+    }
+
+    /// Get the data without checking the key.
+    ///
+    /// # Safety
+    /// `key.id()` must be less than `self.len()`. Keys from this vec can be out of bounds after a
+    /// [`Self::pop`] or [`Self::truncate_to`].
+    pub unsafe fn get_unchecked(&self, key: K)->&T {
+        unsafe {self.inner.get_unchecked(key.id())}
+    }
+
+    /// Get the data mutably without checking the key.
+    ///
+    /// # Safety
+    /// `key.id()` must be less than `self.len()`. Keys from this vec can be out of bounds after a
+    /// [`Self::pop`] or [`Self::truncate_to`].
+    pub unsafe fn get_unchecked_mut(&mut self, key: K)->&mut T {
+        unsafe {self.inner.get_unchecked_mut(key.id())}
+    }
+
     pub fn len(&self)->usize {
         self.inner.len()
     }
+
+    pub fn is_empty(&self)->bool {
+        self.inner.is_empty()
+    }
+
+    /// All the data, indexed by the key ids
+    pub fn as_slice(&self)->&[T] {
+        &self.inner
+    }
+
+    pub fn as_mut_slice(&mut self)->&mut [T] {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self)->Vec<T> {
+        self.inner
+    }
+
+    pub fn iter<'a>(&'a self)->Iter<'a, T> {
+        self.inner.iter()
+    }
+
+    pub fn iter_mut<'a>(&'a mut self)->IterMut<'a, T> {
+        self.inner.iter_mut()
+    }
+
+    pub fn iter_with_keys<'a>(&'a self)->KeyedVecIterWithKeys<'a, K, T> {
+        KeyedVecIterWithKeys {
+            inner: self.inner.iter().enumerate(),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn iter_mut_with_keys<'a>(&'a mut self)->KeyedVecIterMutWithKeys<'a, K, T> {
+        KeyedVecIterMutWithKeys {
+            inner: self.inner.iter_mut().enumerate(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Converts every item, keeping each one at the same key
+    pub fn map<U, F: FnMut(K, T)->U>(self, mut f: F)->KeyedVec<K, U> {
+        KeyedVec {
+            inner: self.inner.into_iter()
+                .enumerate()
+                .map(|(i, t)|f(K::from_id(i), t))
+                .collect(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Same as [`Self::map`], but borrows the items instead of consuming them
+    pub fn map_ref<U, F: FnMut(K, &T)->U>(&self, mut f: F)->KeyedVec<K, U> {
+        KeyedVec {
+            inner: self.inner.iter()
+                .enumerate()
+                .map(|(i, t)|f(K::from_id(i), t))
+                .collect(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Binary searches the data, which must be sorted according to `f`. Returns the key of a
+    /// matching item, or `Err` with the key a matching item could be inserted at to keep the order.
+    pub fn binary_search_by<F: FnMut(&T)->Ordering>(&self, f: F)->Result<K, K> {
+        self.inner.binary_search_by(f)
+            .map(K::from_id)
+            .map_err(K::from_id)
+    }
+
+    /// Iterate over this and a parallel vec with the same keys together. Both should have the
+    /// same length; in release builds iteration stops at the end of the shorter one.
+    pub fn zip<'a, U>(&'a self, other: &'a KeyedVec<K, U>)->impl 'a + DoubleEndedIterator<Item = (K, &'a T, &'a U)> + ExactSizeIterator {
+        debug_assert_eq!(self.inner.len(), other.inner.len(), "Zipped KeyedVecs have different lengths");
+        self.inner.iter()
+            .zip(other.inner.iter())
+            .enumerate()
+            .map(|(i, (t, u))|(K::from_id(i), t, u))
+    }
+
+    /// Same as [`Self::zip`], but this vec's items are mutable
+    pub fn zip_mut<'a, U>(&'a mut self, other: &'a KeyedVec<K, U>)->impl 'a + DoubleEndedIterator<Item = (K, &'a mut T, &'a U)> + ExactSizeIterator {
+        debug_assert_eq!(self.inner.len(), other.inner.len(), "Zipped KeyedVecs have different lengths");
+        self.inner.iter_mut()
+            .zip(other.inner.iter())
+            .enumerate()
+            .map(|(i, (t, u))|(K::from_id(i), t, u))
+    }
+
+    /// Stable sorts the data with `cmp`. The returned remap gives the new key of every old key, so
+    /// references stored elsewhere can be fixed up.
+    pub fn sort_by_key_remap<F: FnMut(&T, &T)->Ordering>(&mut self, mut cmp: F)->Remap<K> {
+        let mut order = (0..self.inner.len()).collect::<Vec<_>>();
+        order.sort_by(|a, b|cmp(&self.inner[*a], &self.inner[*b]));
+
+        let mut new_ids = vec![0; order.len()];
+        for (new, old) in order.iter().enumerate() {
+            new_ids[*old] = new;
+        }
+
+        let mut old = std::mem::take(&mut self.inner)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.inner = order.into_iter()
+            .map(|i|old[i].take().unwrap())
+            .collect();
+
+        return Remap::from_new_ids(new_ids);
+    }
+
+    /// Iterate over every key in the vec, in order
+    pub fn keys(&self)->KeyedVecKeys<K> {
+        KeyedVecKeys {
+            inner: 0..self.inner.len(),
+            _phantom: PhantomData,
+        }
+    }
 }
 impl<K: Key, T> Index<K> for KeyedVec<K, T> {
     type Output = T;
@@ -60,3 +311,126 @@ impl<K: Key, T> IndexMut<K> for KeyedVec<K, T> {
         self.get_mut(key)
     }
 }
+/// The data of a contiguous run of keys, such as one returned by [`KeyedVec::extend_and_keys`]
+impl<K: Key, T> Index<KeyRange<K>> for KeyedVec<K, T> {
+    type Output = [T];
+    #[inline]
+    fn index(&self, range: KeyRange<K>)->&[T] {
+        &self.inner[range.as_range()]
+    }
+}
+impl<K: Key, T> IndexMut<KeyRange<K>> for KeyedVec<K, T> {
+    #[inline]
+    fn index_mut(&mut self, range: KeyRange<K>)->&mut [T] {
+        &mut self.inner[range.as_range()]
+    }
+}
+/// Manual impls so `K` doesn't need to implement them
+impl<K: Key, T: PartialEq> PartialEq for KeyedVec<K, T> {
+    fn eq(&self, other: &Self)->bool {
+        self.inner == other.inner
+    }
+}
+impl<K: Key, T: Eq> Eq for KeyedVec<K, T> {}
+impl<K: Key, T: Hash> Hash for KeyedVec<K, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner.hash(state);
+    }
+}
+impl<K: Key, T> FromIterator<T> for KeyedVec<K, T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I)->Self {
+        KeyedVec {
+            inner: Vec::from_iter(iter),
+            _phantom: PhantomData,
+        }
+    }
+}
+impl<K: Key, T> Extend<T> for KeyedVec<K, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.inner.extend(iter);
+    }
+}
+impl<K: Key, T> IntoIterator for KeyedVec<K, T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+    fn into_iter(self)->Self::IntoIter {
+        self.inner.into_iter()
+    }
+}
+impl<'a, K: Key, T> IntoIterator for &'a KeyedVec<K, T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self)->Self::IntoIter {
+        self.inner.iter()
+    }
+}
+impl<'a, K: Key, T> IntoIterator for &'a mut KeyedVec<K, T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+    fn into_iter(self)->Self::IntoIter {
+        self.inner.iter_mut()
+    }
+}
+
+pub struct KeyedVecIterWithKeys<'a, K: Key, T: 'a> {
+    inner: Enumerate<Iter<'a, T>>,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, T: 'a> Iterator for KeyedVecIterWithKeys<'a, K, T> {
+    type Item = (K, &'a T);
+    fn next(&mut self)->Option<Self::Item> {
+        self.inner.next().map(|(i, t)|(K::from_id(i), t))
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<'a, K: Key, T: 'a> DoubleEndedIterator for KeyedVecIterWithKeys<'a, K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        self.inner.next_back().map(|(i, t)|(K::from_id(i), t))
+    }
+}
+impl<'a, K: Key, T: 'a> ExactSizeIterator for KeyedVecIterWithKeys<'a, K, T> {}
+
+pub struct KeyedVecIterMutWithKeys<'a, K: Key, T: 'a> {
+    inner: Enumerate<IterMut<'a, T>>,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, T: 'a> Iterator for KeyedVecIterMutWithKeys<'a, K, T> {
+    type Item = (K, &'a mut T);
+    fn next(&mut self)->Option<Self::Item> {
+        self.inner.next().map(|(i, t)|(K::from_id(i), t))
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<'a, K: Key, T: 'a> DoubleEndedIterator for KeyedVecIterMutWithKeys<'a, K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        self.inner.next_back().map(|(i, t)|(K::from_id(i), t))
+    }
+}
+impl<'a, K: Key, T: 'a> ExactSizeIterator for KeyedVecIterMutWithKeys<'a, K, T> {}
+
+pub struct KeyedVecKeys<K: Key> {
+    inner: Range<usize>,
+    _phantom: PhantomData<K>,
+}
+impl<K: Key> Iterator for KeyedVecKeys<K> {
+    type Item = K;
+    fn next(&mut self)->Option<K> {
+        self.inner.next().map(K::from_id)
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<K: Key> DoubleEndedIterator for KeyedVecKeys<K> {
+    fn next_back(&mut self)->Option<K> {
+        self.inner.next_back().map(K::from_id)
+    }
+}
+impl<K: Key> ExactSizeIterator for KeyedVecKeys<K> {}
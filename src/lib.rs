@@ -1,23 +1,66 @@
+#![allow(
+    clippy::needless_return,
+    clippy::new_without_default,
+    clippy::len_without_is_empty,
+    clippy::while_let_on_iterator,
+    clippy::match_like_matches_macro,
+    clippy::single_match,
+)]
+
+
 use serde::{Serialize, Deserialize};
 use std::{
     ops::{
         Add,
+        BitOr,
         Range,
         RangeInclusive,
     },
     cmp::Ordering,
+    fmt::{
+        self,
+        Debug,
+        Display,
+        Formatter,
+    },
+    marker::PhantomData,
+    path::Path,
 };
 
 pub use sparse_list::SparseList;
 pub use keyed_vec::KeyedVec;
 pub use slotmap::SlotMap;
 pub use stack::Stack;
+pub use gen_slotmap::{GenSlotMap, GenKey};
+pub use secondary_map::{SecondaryMap, SparseSecondaryMap};
+pub use dense_slotmap::DenseSlotMap;
+pub use concurrent_slotmap::ConcurrentSlotMap;
+pub use observed_slotmap::{ObservedSlotMap, SlotMapObserver};
+pub use compact_sparse_list::CompactSparseList;
+pub use chunked_keyed_vec::ChunkedKeyedVec;
+pub use interning_keyed_vec::InterningKeyedVec;
+pub use min_max_stack::MinMaxStack;
+pub use array_stack::ArrayStack;
+pub use small_stack::SmallStack;
 
 
 pub mod sparse_list;
 pub mod keyed_vec;
 pub mod slotmap;
 pub mod stack;
+pub mod gen_slotmap;
+pub mod secondary_map;
+pub mod dense_slotmap;
+pub mod concurrent_slotmap;
+pub mod observed_slotmap;
+pub mod compact_sparse_list;
+pub mod chunked_keyed_vec;
+pub mod interning_keyed_vec;
+pub mod min_max_stack;
+pub mod array_stack;
+pub mod small_stack;
+
+mod bitmap;
 
 
 #[macro_export]
@@ -43,6 +86,35 @@ macro_rules! define_keys {
 }
 
 
+/// Same as [`define_keys`], but the keys hold a `u32` instead of a `usize`. This halves the size
+/// of the keys on 64-bit targets, and since the maps store keys directly (free lists, secondary
+/// tables, etc.) it halves that memory too. Creating a key from an id that doesn't fit in a `u32`
+/// panics.
+#[macro_export]
+macro_rules! define_keys32 {
+    ($first:ident $(,$name:ident)*)=>{
+        $crate::define_keys32!($first, $($name,)*);
+    };
+
+    ($($name:ident,)*)=>{
+        $(
+            #[repr(transparent)]
+            #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+            pub struct $name(pub u32);
+            impl $crate::Key for $name {
+                fn from_id(id: usize)->Self {
+                    $name(u32::try_from(id).expect("Key id does not fit in a u32"))
+                }
+                fn id(&self)->usize {self.0 as usize}
+            }
+            impl $name {
+                pub fn invalid()->Self {Self(u32::MAX)}
+            }
+        )*
+    };
+}
+
+
 pub trait Key {
     fn from_id(id: usize)->Self;
     fn id(&self)->usize;
@@ -52,10 +124,144 @@ impl Key for usize {
     fn from_id(id: usize)->Self {id}
     fn id(&self)->usize {*self}
 }
+impl Key for u32 {
+    fn from_id(id: usize)->Self {u32::try_from(id).expect("Key id does not fit in a u32")}
+    fn id(&self)->usize {*self as usize}
+}
+
+
+/// A contiguous range of keys `start..end`. Like [`Span`], but typed with the key it came from.
+pub struct KeyRange<K: Key> {
+    start: usize,
+    end: usize,
+    _phantom: PhantomData<K>,
+}
+impl<K: Key> KeyRange<K> {
+    pub fn new(start: K, end: K)->Self {
+        KeyRange {
+            start: start.id(),
+            end: end.id(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The first key in the range
+    pub fn start(&self)->K {
+        K::from_id(self.start)
+    }
+
+    /// The key just past the end of the range
+    pub fn end(&self)->K {
+        K::from_id(self.end)
+    }
+
+    pub fn len(&self)->usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.start >= self.end
+    }
+
+    pub fn contains(&self, key: &K)->bool {
+        key.id() >= self.start && key.id() < self.end
+    }
+
+    /// The ids of the keys in the range
+    pub fn as_range(&self)->Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn iter(&self)->impl DoubleEndedIterator<Item = K> + ExactSizeIterator {
+        self.as_range().map(K::from_id)
+    }
+}
+impl<K: Key> Clone for KeyRange<K> {
+    fn clone(&self)->Self {
+        *self
+    }
+}
+impl<K: Key> Copy for KeyRange<K> {}
+impl<K: Key> PartialEq for KeyRange<K> {
+    fn eq(&self, other: &Self)->bool {
+        self.start == other.start && self.end == other.end
+    }
+}
+impl<K: Key> Eq for KeyRange<K> {}
+impl<K: Key> Debug for KeyRange<K> {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        write!(f, "KeyRange({}..{})", self.start, self.end)
+    }
+}
+
+
+/// How far the keys of an appended container moved. Use [`Self::translate`] to turn a key from the
+/// appended container into the equivalent key in the one it was appended to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyOffset(pub usize);
+impl KeyOffset {
+    pub fn translate<K: Key>(&self, key: K)->K {
+        K::from_id(key.id() + self.0)
+    }
+}
+
+
+/// Maps every old key of a reordered container to its new key
+pub struct Remap<K: Key> {
+    /// The new id of each old id
+    new_ids: Vec<usize>,
+    _phantom: PhantomData<K>,
+}
+impl<K: Key> Remap<K> {
+    pub(crate) fn from_new_ids(new_ids: Vec<usize>)->Self {
+        Remap {
+            new_ids,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The new key for the old key. Panics if the key wasn't in the container.
+    pub fn get(&self, old: K)->K {
+        K::from_id(self.new_ids[old.id()])
+    }
+
+    pub fn try_get(&self, old: K)->Option<K> {
+        self.new_ids.get(old.id()).copied().map(K::from_id)
+    }
+
+    pub fn len(&self)->usize {
+        self.new_ids.len()
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.new_ids.is_empty()
+    }
+
+    /// Iterate over all `(old_key, new_key)` pairs in old key order
+    pub fn iter<'a>(&'a self)->impl 'a + DoubleEndedIterator<Item = (K, K)> + ExactSizeIterator {
+        self.new_ids.iter()
+            .enumerate()
+            .map(|(old, new)|(K::from_id(old), K::from_id(*new)))
+    }
+}
+impl<K: Key> Clone for Remap<K> {
+    fn clone(&self)->Self {
+        Remap::from_new_ids(self.new_ids.clone())
+    }
+}
+impl<K: Key> Debug for Remap<K> {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        f.debug_map()
+            .entries(self.new_ids.iter().enumerate())
+            .finish()
+    }
+}
 
 
 /// A range. Basically [`Range`], but impements [`Copy`] and only uses [`usize`]
-#[derive(Copy, Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+///
+/// Spans are ordered by `start` first, then by `end`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub struct Span {
     /// Inclusive
     pub start: usize,
@@ -78,14 +284,116 @@ impl From<RangeInclusive<usize>> for Span {
         }
     }
 }
+/// Collects a set of indices into the smallest span covering all of them. An empty iterator
+/// produces the empty default span.
+impl FromIterator<usize> for Span {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I)->Self {
+        let mut iter = iter.into_iter();
+        let Some(first) = iter.next() else {return Span::default()};
+
+        let (start, end) = iter.fold((first, first), |(start, end), i|(start.min(i), end.max(i)));
+
+        return Span(start, end + 1);
+    }
+}
+/// Same as [`Span::union`]
+impl Add for Span {
+    type Output = Self;
+    fn add(self, other: Self)->Self {
+        self.union(other)
+    }
+}
+/// Same as [`Span::union`]
+impl BitOr for Span {
+    type Output = Self;
+    fn bitor(self, other: Self)->Self {
+        self.union(other)
+    }
+}
+/// Shows the span as `start..end`
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
 impl Span {
     pub fn contains(&self, i: usize)->bool {
         i >= self.start && i < self.end
     }
+
+    pub fn len(&self)->usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.start >= self.end
+    }
+
+    /// The part of `source` the span covers. Returns `None` if the span is out of bounds or
+    /// doesn't start and end on char boundaries.
+    pub fn slice<'a>(&self, source: &'a str)->Option<&'a str> {
+        source.get(self.start..self.end)
+    }
+
+    /// Moves the span forward by `offset`, such as from a fragment of the source into the whole
+    /// source. Panics on overflow.
+    pub fn shifted(self, offset: usize)->Self {
+        let start = self.start.checked_add(offset).expect("Span shifted past usize::MAX");
+        let end = self.end.checked_add(offset).expect("Span shifted past usize::MAX");
+
+        return Span(start, end);
+    }
+
+    /// Moves the span by `offset` in either direction. Returns `None` if either end would
+    /// overflow or go below zero.
+    pub fn shift_by(self, offset: isize)->Option<Self> {
+        let start = self.start.checked_add_signed(offset)?;
+        let end = self.end.checked_add_signed(offset)?;
+
+        return Some(Span(start, end));
+    }
+
+    /// The span relative to the start of `outer`, the opposite of [`Self::shifted`]. Returns `None`
+    /// if the span isn't inside `outer`.
+    pub fn relative_to(self, outer: Self)->Option<Self> {
+        if !outer.contains_span(&self) {return None}
+
+        return Some(Span(self.start - outer.start, self.end - outer.start));
+    }
+
+    /// The smallest span covering both spans, including any gap between them
+    pub fn union(self, other: Self)->Self {
+        Span(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// The smallest span covering all the spans. Returns `None` if there are no spans.
+    pub fn union_all<I: IntoIterator<Item = Span>>(spans: I)->Option<Self> {
+        spans.into_iter().reduce(Span::union)
+    }
+
+    /// The part of the span that is also in `other`. Returns `None` if they don't share any
+    /// indices, so spans that only touch don't intersect.
+    pub fn intersect(self, other: Self)->Option<Self> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+        if start >= end {return None}
+
+        return Some(Span(start, end));
+    }
+
+    /// If the spans share at least one index
+    pub fn overlaps(&self, other: &Self)->bool {
+        self.intersect(*other).is_some()
+    }
+
+    /// If all of `other` is inside this span
+    pub fn contains_span(&self, other: &Self)->bool {
+        other.start >= self.start && other.end <= self.end
+    }
 }
 
 /// Line and column are zero-based
-#[derive(Copy, Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 pub struct Location {
     pub span: Span,
     pub line: usize,
@@ -93,47 +401,92 @@ pub struct Location {
     pub column: usize,
     pub end_column: usize,
 }
+/// Same as [`Location::merge`]
 impl Add for Location {
     type Output = Self;
     fn add(self, other: Self)->Self {
-        let span_start = self.span.start.min(other.span.start);
-        let span_end = self.span.end.min(other.span.end);
-
-        let line;
-        let column;
-        if self.line < other.line {
-            line = self.line;
-            column = self.column;
-        } else {
-            line = other.line;
-            column = other.column;
+        self.merge(other)
+    }
+}
+/// Shows the location as `line:column..end_line:end_column`, zero-based. Use
+/// [`Location::one_based`] for the form editors and compilers usually show.
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        write!(f, "{}:{}..{}:{}", self.line, self.column, self.end_line, self.end_column)
+    }
+}
+impl PartialOrd for Location {
+    fn partial_cmp(&self, o: &Self)->Option<Ordering> {
+        if self.line == o.line {
+            return self.column.partial_cmp(&o.column);
         }
+        return self.line.partial_cmp(&o.line);
+    }
+}
+impl Location {
+    /// Displays the location with one-based lines and columns
+    pub fn one_based(&self)->OneBasedLocation {
+        OneBasedLocation(*self)
+    }
 
-        let end_line;
-        let end_column;
-        if self.end_line > other.end_line {
-            end_line = self.end_line;
-            end_column = self.end_column;
-        } else {
-            end_line = other.end_line;
-            end_column = other.end_column;
+    /// Displays the start of the location as `path:line:column`, one-based, like compiler errors
+    pub fn display_with_path<'a, P: AsRef<Path> + ?Sized>(&self, path: &'a P)->LocationWithPath<'a> {
+        LocationWithPath {
+            location: *self,
+            path: path.as_ref(),
         }
+    }
+
+    /// The smallest location covering both locations, including any gap between them
+    pub fn merge(self, other: Self)->Self {
+        let (line, column) = (self.line, self.column).min((other.line, other.column));
+        let (end_line, end_column) = (self.end_line, self.end_column).max((other.end_line, other.end_column));
 
         Location {
-            span: Span(span_start, span_end),
+            span: self.span.union(other.span),
             line,
             end_line,
             column,
             end_column,
         }
     }
+
+    /// Splits the location at the start of `line`, returning the head (everything before `line`)
+    /// and the tail (`line` onward). Line and column math alone can't recover the byte span of
+    /// the boundary, so the `converter` for the same source is required to compute exact spans
+    /// for both halves.
+    ///
+    /// Panics if `line` is not in `self.line + 1..=self.end_line`.
+    pub fn split_at_line(&self, line: usize, converter: &SpanConverter)->(Location, Location) {
+        assert!(line > self.line && line <= self.end_line, "Line {} is not inside the location", line);
+
+        let boundary = converter.line_spans[line].start;
+        let head = converter.convert(Span(self.span.start, boundary));
+        let tail = converter.convert(Span(boundary, self.span.end));
+
+        return (head, tail);
+    }
 }
-impl PartialOrd for Location {
-    fn partial_cmp(&self, o: &Self)->Option<Ordering> {
-        if self.line == o.line {
-            return self.column.partial_cmp(&o.column);
-        }
-        return self.line.partial_cmp(&o.line);
+
+/// Shows a [`Location`] as `line:column..end_line:end_column`, one-based
+#[derive(Copy, Clone, Debug)]
+pub struct OneBasedLocation(pub Location);
+impl Display for OneBasedLocation {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        let l = &self.0;
+        write!(f, "{}:{}..{}:{}", l.line + 1, l.column + 1, l.end_line + 1, l.end_column + 1)
+    }
+}
+
+/// Shows the start of a [`Location`] as `path:line:column`, one-based
+#[derive(Copy, Clone, Debug)]
+pub struct LocationWithPath<'a> {
+    location: Location,
+    path: &'a Path,
+}
+impl<'a> Display for LocationWithPath<'a> {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        write!(f, "{}:{}:{}", self.path.display(), self.location.line + 1, self.location.column + 1)
     }
 }
 
@@ -162,34 +515,131 @@ impl SpanConverter {
         }
     }
 
-    /// Converts a Span to a LocationSpan
-    pub fn convert(&self, span: Span)->Location {
-        let mut start = None;
-        let mut end = None;
-        for (i, line_span) in self.line_spans.iter().enumerate() {
-            if line_span.contains(span.start) {
-                start = Some((i, span.start - line_span.start));
-            }
-            if line_span.contains(span.end) {
-                end = Some((i, span.end - line_span.start));
+    /// The line containing `offset`, searching from line `from` onward. The offset just past the
+    /// end of the source is on the last line.
+    fn line_of(&self, offset: usize, from: usize)->Option<usize> {
+        let line = from + self.line_spans[from..].partition_point(|l|l.end <= offset);
+        if line < self.line_spans.len() && self.line_spans[line].contains(offset) {
+            return Some(line);
+        }
 
-                break;
-            }
+        let last = self.line_spans.len() - 1;
+        if offset == self.line_spans[last].end {
+            return Some(last);
         }
 
-        let start = start.unwrap();
-        let end = end.unwrap();
+        return None;
+    }
+
+    fn convert_from(&self, span: Span, from: usize)->Option<Location> {
+        let line = self.line_of(span.start, from)?;
+        let end_line = self.line_of(span.end, line)?;
 
-        return Location {
+        return Some(Location {
             span,
-            line: start.0,
-            end_line: end.0,
-            column: start.1,
-            end_column: end.1,
+            line,
+            end_line,
+            column: span.start - self.line_spans[line].start,
+            end_column: span.end - self.line_spans[end_line].start,
+        });
+    }
+
+    /// Converts a Span to a LocationSpan. Panics if the span is outside the source.
+    pub fn convert(&self, span: Span)->Location {
+        self.try_convert(span).expect("Span is outside the source")
+    }
+
+    /// Converts a Span to a LocationSpan. Returns `None` if the span is outside the source or ends
+    /// before it starts. The offset just past the end of the source is valid, so spans at the end
+    /// of the file can be converted.
+    pub fn try_convert(&self, span: Span)->Option<Location> {
+        if span.end < span.start {return None}
+
+        self.convert_from(span, 0)
+    }
+
+    /// The number of lines. A trailing newline starts a last, empty line.
+    pub fn line_count(&self)->usize {
+        self.line_spans.len()
+    }
+
+    /// The span of the zero-based `line`, including its line ending
+    pub fn line_span(&self, line: usize)->Option<Span> {
+        self.line_spans.get(line).copied()
+    }
+
+    /// The text of the zero-based `line` without its `\n` or `\r\n`. `source` must be the
+    /// source this converter was made from.
+    pub fn line_text<'a>(&self, source: &'a str, line: usize)->Option<&'a str> {
+        let text = self.line_span(line)?.slice(source)?;
+        return Some(match text.strip_suffix('\n') {
+            Some(text)=>text.strip_suffix('\r').unwrap_or(text),
+            None=>text,
+        });
+    }
+
+    /// The byte offset of the zero-based `line` and `column`. Returns `None` if the column is past
+    /// the end of the line. The offset just past the end of the source is valid.
+    pub fn offset_of(&self, line: usize, column: usize)->Option<usize> {
+        let line_span = self.line_spans.get(line)?;
+        let offset = line_span.start.checked_add(column)?;
+
+        let is_last = line == self.line_spans.len() - 1;
+        if line_span.contains(offset) || (is_last && offset == line_span.end) {
+            return Some(offset);
+        }
+
+        return None;
+    }
+
+    /// The byte span of the location's lines and columns, the opposite of [`Self::convert`]. The
+    /// location's own `span` is ignored. Panics if the location is outside the source.
+    pub fn span_of(&self, location: &Location)->Span {
+        let start = self.offset_of(location.line, location.column)
+            .expect("Location start is outside the source");
+        let end = self.offset_of(location.end_line, location.end_column)
+            .expect("Location end is outside the source");
+
+        return Span(start, end);
+    }
+
+    /// Same as [`Self::convert`], but the columns count `char`s instead of bytes, which matches
+    /// what most editors show for non-ASCII source. The span stays in bytes for slicing. `source`
+    /// must be the source this converter was made from.
+    ///
+    /// Panics if the span is outside the source or not on char boundaries.
+    pub fn convert_chars(&self, source: &str, span: Span)->Location {
+        let mut location = self.convert(span);
+
+        let char_column = |line: usize, offset: usize|{
+            Span(self.line_spans[line].start, offset)
+                .slice(source)
+                .expect("Span is not on char boundaries")
+                .chars()
+                .count()
         };
+        location.column = char_column(location.line, span.start);
+        location.end_column = char_column(location.end_line, span.end);
+
+        return location;
     }
-}
 
+    /// Converts many spans at once. This is faster when the spans are sorted by their start, but
+    /// works for any order.
+    pub fn convert_many<'a, I: 'a + IntoIterator<Item = Span>>(&'a self, spans: I)->impl 'a + Iterator<Item = Location> {
+        let mut prev_line = 0;
+        spans.into_iter().map(move |span|{
+            if span.start < self.line_spans[prev_line].start {
+                prev_line = 0;
+            }
+
+            let location = self.convert_from(span, prev_line).expect("Span is outside the source");
+            prev_line = location.line;
+
+            return location;
+        })
+    }
+}
 
 /// Convenient way to allow for a `Span(start, end)` constructor, but still have `span.start` and
 /// `span.end` fields.
@@ -197,3 +647,29 @@ impl SpanConverter {
 pub fn Span(start: usize, end: usize)->Span {
     Span {start, end}
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_at_middle_line() {
+        let source = "ab\ncd\nef";
+        let converter = SpanConverter::new(source);
+        let location = converter.convert(Span(1, 7));
+        assert_eq!((location.line, location.end_line), (0, 2));
+
+        let (head, tail) = location.split_at_line(1, &converter);
+        assert_eq!(head.span, Span(1, 3));
+        assert_eq!((head.line, head.column, head.end_line, head.end_column), (0, 1, 1, 0));
+        assert_eq!(tail.span, Span(3, 7));
+        assert_eq!((tail.line, tail.column, tail.end_line, tail.end_column), (1, 0, 2, 1));
+    }
+
+    #[test]
+    fn span_from_indices() {
+        assert_eq!([3, 7, 5].into_iter().collect::<Span>(), Span(3, 8));
+        assert_eq!(std::iter::empty().collect::<Span>(), Span::default());
+    }
+}
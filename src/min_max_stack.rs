@@ -0,0 +1,97 @@
+/// A [`crate::stack::Stack`] that also tracks the smallest and largest item, so [`Self::min`] and
+/// [`Self::max`] don't need to search the whole stack. Equal items count as the earliest one
+/// pushed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinMaxStack<T: Ord> {
+    items: Vec<T>,
+    /// The indexes of the smallest and largest items at or below each item
+    extremes: Vec<(usize, usize)>,
+}
+impl<T: Ord> MinMaxStack<T> {
+    pub fn new()->Self {
+        MinMaxStack {
+            items: Vec::new(),
+            extremes: Vec::new(),
+        }
+    }
+
+    /// Creates a new stack with at least `capacity` empty slots
+    pub fn with_capacity(capacity: usize)->Self {
+        MinMaxStack {
+            items: Vec::with_capacity(capacity),
+            extremes: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        let index = self.items.len();
+        let extremes = match self.extremes.last() {
+            Some(&(min, max))=>(
+                if item < self.items[min] {index} else {min},
+                if item > self.items[max] {index} else {max},
+            ),
+            None=>(index, index),
+        };
+
+        self.items.push(item);
+        self.extremes.push(extremes);
+    }
+
+    pub fn pop(&mut self)->Option<T> {
+        self.extremes.pop();
+        self.items.pop()
+    }
+
+    pub fn last(&self)->Option<&T> {
+        self.items.last()
+    }
+
+    /// The smallest item on the stack
+    pub fn min(&self)->Option<&T> {
+        let (min, _) = self.extremes.last()?;
+        return Some(&self.items[*min]);
+    }
+
+    /// The largest item on the stack
+    pub fn max(&self)->Option<&T> {
+        let (_, max) = self.extremes.last()?;
+        return Some(&self.items[*max]);
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.extremes.clear();
+    }
+
+    /// Iterate from the top of the stack to the bottom.
+    pub fn iter<'a>(&'a self)->impl 'a + Iterator<Item = &'a T> {
+        self.items.iter().rev()
+    }
+
+    pub fn len(&self)->usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.items.is_empty()
+    }
+}
+impl<T: Ord> Default for MinMaxStack<T> {
+    fn default()->Self {
+        Self::new()
+    }
+}
+impl<T: Ord> Extend<T> for MinMaxStack<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+impl<T: Ord> FromIterator<T> for MinMaxStack<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I)->Self {
+        let mut stack = Self::new();
+        stack.extend(iter);
+        return stack;
+    }
+}
@@ -0,0 +1,97 @@
+use std::ops::Deref;
+use crate::{
+    Key,
+    slotmap::SlotMap,
+};
+
+
+/// Hooks that an [`ObservedSlotMap`] calls whenever data is added or removed. Both default to
+/// doing nothing, so only the interesting ones need to be implemented.
+pub trait SlotMapObserver<K: Key, T> {
+    /// Called after the data has been inserted with the key it was given
+    fn on_insert(&mut self, _key: &K, _data: &T) {}
+
+    /// Called after the data has been removed, before it is returned to the caller
+    fn on_remove(&mut self, _key: &K, _data: &T) {}
+}
+impl<K: Key, T> SlotMapObserver<K, T> for () {}
+
+
+/// A [`SlotMap`] that tells an observer about every insert and removal, so indexes derived from
+/// the data (a by-name lookup for example) can be kept in sync. Read-only access to the map is
+/// available through `Deref`.
+#[derive(Debug, Clone)]
+pub struct ObservedSlotMap<K: Key, T, O: SlotMapObserver<K, T>> {
+    map: SlotMap<K, T>,
+    observer: O,
+}
+impl<K: Key, T, O: SlotMapObserver<K, T>> ObservedSlotMap<K, T, O> {
+    pub fn new(observer: O)->Self {
+        ObservedSlotMap {
+            map: SlotMap::new(),
+            observer,
+        }
+    }
+
+    /// Wraps an existing map. The observer is NOT told about the data already in the map.
+    pub fn from_parts(map: SlotMap<K, T>, observer: O)->Self {
+        ObservedSlotMap {
+            map,
+            observer,
+        }
+    }
+
+    pub fn into_parts(self)->(SlotMap<K, T>, O) {
+        (self.map, self.observer)
+    }
+
+    pub fn observer(&self)->&O {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self)->&mut O {
+        &mut self.observer
+    }
+
+    pub fn insert(&mut self, data: T)->K {
+        let key = self.map.insert(data);
+        self.observer.on_insert(&key, &self.map[K::from_id(key.id())]);
+
+        return key;
+    }
+
+    #[inline]
+    pub fn reserve_slot(&mut self)->K {
+        self.map.reserve_slot()
+    }
+
+    /// Returns Err(data) when the key DOES NOT point to a valid reserved entry. The observer is
+    /// only called if the data was inserted.
+    pub fn insert_reserved(&mut self, key: K, data: T)->Result<(), T> {
+        let id = key.id();
+        self.map.insert_reserved(key, data)?;
+        self.observer.on_insert(&K::from_id(id), &self.map[K::from_id(id)]);
+
+        return Ok(());
+    }
+
+    pub fn remove(&mut self, key: K)->Option<T> {
+        let id = key.id();
+        let data = self.map.remove(key)?;
+        self.observer.on_remove(&K::from_id(id), &data);
+
+        return Some(data);
+    }
+
+    /// Mutable access to the data. The observer is NOT told about changes made through this, so
+    /// don't change anything the observer's derived data depends on.
+    pub fn get_mut(&mut self, key: K)->Option<&mut T> {
+        self.map.get_mut(key)
+    }
+}
+impl<K: Key, T, O: SlotMapObserver<K, T>> Deref for ObservedSlotMap<K, T, O> {
+    type Target = SlotMap<K, T>;
+    fn deref(&self)->&SlotMap<K, T> {
+        &self.map
+    }
+}
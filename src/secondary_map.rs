@@ -0,0 +1,206 @@
+use serde::{Serialize, Deserialize};
+use std::{
+    ops::{
+        Index,
+        IndexMut,
+    },
+    slice::Iter,
+    iter::Enumerate,
+    marker::PhantomData,
+    collections::{
+        HashMap,
+        hash_map,
+    },
+};
+use crate::Key;
+
+
+/// Associates extra data with the keys of another map (like [`crate::slotmap::SlotMap`]) without
+/// storing it in that map. Grows to fit whatever keys are inserted, so it doesn't need to know
+/// about the primary map at all.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SecondaryMap<K: Key, V> {
+    inner: Vec<Option<V>>,
+    len: usize,
+    _phantom: PhantomData<K>,
+}
+impl<K: Key, V> SecondaryMap<K, V> {
+    pub fn new()->Self {
+        SecondaryMap {
+            inner: Vec::new(),
+            len: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Inserts the data for the key, returning the old data if there was any
+    pub fn insert(&mut self, key: K, data: V)->Option<V> {
+        let id = key.id();
+        if id >= self.inner.len() {
+            self.inner.resize_with(id + 1, ||None);
+        }
+
+        let old = self.inner[id].replace(data);
+        if old.is_none() {
+            self.len += 1;
+        }
+
+        return old;
+    }
+
+    pub fn get(&self, key: K)->Option<&V> {
+        self.inner.get(key.id())?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, key: K)->Option<&mut V> {
+        self.inner.get_mut(key.id())?.as_mut()
+    }
+
+    pub fn contains_key(&self, key: K)->bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: K)->Option<V> {
+        let old = self.inner.get_mut(key.id())?.take();
+        if old.is_some() {
+            self.len -= 1;
+        }
+
+        return old;
+    }
+
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        self.len = 0;
+    }
+
+    /// The number of keys with data
+    pub fn len(&self)->usize {
+        self.len
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.len == 0
+    }
+
+    /// Iterate over all `(key, value)` pairs in key order
+    pub fn iter<'a>(&'a self)->SecondaryMapIter<'a, K, V> {
+        SecondaryMapIter {
+            inner: self.inner.iter().enumerate(),
+            _phantom: PhantomData,
+        }
+    }
+}
+impl<K: Key, V> Index<K> for SecondaryMap<K, V> {
+    type Output = V;
+    #[inline]
+    fn index(&self, key: K)->&V {
+        self.get(key).unwrap()
+    }
+}
+impl<K: Key, V> IndexMut<K> for SecondaryMap<K, V> {
+    #[inline]
+    fn index_mut(&mut self, key: K)->&mut V {
+        self.get_mut(key).unwrap()
+    }
+}
+
+pub struct SecondaryMapIter<'a, K: Key, V: 'a> {
+    inner: Enumerate<Iter<'a, Option<V>>>,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, V: 'a> Iterator for SecondaryMapIter<'a, K, V> {
+    type Item = (K, &'a V);
+    fn next(&mut self)->Option<Self::Item> {
+        while let Some((i, o_v)) = self.inner.next() {
+            if let Some(v) = o_v {
+                return Some((K::from_id(i), v));
+            }
+        }
+
+        return None;
+    }
+}
+
+
+/// Same as [`SecondaryMap`], but backed by a [`HashMap`] so it only uses memory for the keys that
+/// actually have data. Iteration order is unspecified.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SparseSecondaryMap<K: Key, V> {
+    inner: HashMap<usize, V>,
+    _phantom: PhantomData<K>,
+}
+impl<K: Key, V> SparseSecondaryMap<K, V> {
+    pub fn new()->Self {
+        SparseSecondaryMap {
+            inner: HashMap::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Inserts the data for the key, returning the old data if there was any
+    pub fn insert(&mut self, key: K, data: V)->Option<V> {
+        self.inner.insert(key.id(), data)
+    }
+
+    pub fn get(&self, key: K)->Option<&V> {
+        self.inner.get(&key.id())
+    }
+
+    pub fn get_mut(&mut self, key: K)->Option<&mut V> {
+        self.inner.get_mut(&key.id())
+    }
+
+    pub fn contains_key(&self, key: K)->bool {
+        self.inner.contains_key(&key.id())
+    }
+
+    pub fn remove(&mut self, key: K)->Option<V> {
+        self.inner.remove(&key.id())
+    }
+
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    /// The number of keys with data
+    pub fn len(&self)->usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.inner.is_empty()
+    }
+
+    /// Iterate over all `(key, value)` pairs in an unspecified order
+    pub fn iter<'a>(&'a self)->SparseSecondaryMapIter<'a, K, V> {
+        SparseSecondaryMapIter {
+            inner: self.inner.iter(),
+            _phantom: PhantomData,
+        }
+    }
+}
+impl<K: Key, V> Index<K> for SparseSecondaryMap<K, V> {
+    type Output = V;
+    #[inline]
+    fn index(&self, key: K)->&V {
+        self.get(key).unwrap()
+    }
+}
+impl<K: Key, V> IndexMut<K> for SparseSecondaryMap<K, V> {
+    #[inline]
+    fn index_mut(&mut self, key: K)->&mut V {
+        self.get_mut(key).unwrap()
+    }
+}
+
+pub struct SparseSecondaryMapIter<'a, K: Key, V: 'a> {
+    inner: hash_map::Iter<'a, usize, V>,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, V: 'a> Iterator for SparseSecondaryMapIter<'a, K, V> {
+    type Item = (K, &'a V);
+    fn next(&mut self)->Option<Self::Item> {
+        self.inner.next().map(|(id, v)|(K::from_id(*id), v))
+    }
+}
@@ -1,9 +1,32 @@
 use serde::{Serialize, Deserialize};
-use std::ops::{
-    Index,
-    IndexMut,
+use std::{
+    fmt::{
+        self,
+        Debug,
+        Display,
+        Formatter,
+    },
+    ops::{
+        Index,
+        IndexMut,
+    },
+    slice::{
+        Iter,
+        IterMut,
+    },
+    iter::{
+        Enumerate,
+        IntoIterator,
+        FromIterator,
+        Extend,
+    },
+    marker::PhantomData,
+    collections::VecDeque,
+};
+use crate::{
+    Key,
+    bitmap::Bitmap,
 };
-use crate::Key;
 
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -20,6 +43,15 @@ impl<T> Slot<T> {
         }
     }
 
+    /// Like `take`, but leaves reserved slots reserved
+    pub fn take_value(&mut self)->Option<T> {
+        if self.has_data() {
+            self.take()
+        } else {
+            None
+        }
+    }
+
     #[inline]
     pub fn insert(&mut self, data: T) {
         *self = Self::Value(data);
@@ -55,18 +87,111 @@ impl<T> Slot<T> {
 }
 
 
+/// Why a [`SlotMap`] failed validation. Holds the offending slot id where there is one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidSlotMap {
+    /// A free key points past the end of the slots
+    FreeKeyOutOfBounds(usize),
+    /// A free key points at an occupied or reserved slot
+    FreeKeyInUse(usize),
+    /// The same key is on the free list more than once
+    DuplicateFreeKey(usize),
+    /// The stored length does not match the number of occupied slots
+    LengthMismatch {
+        stored: usize,
+        actual: usize,
+    },
+}
+impl Display for InvalidSlotMap {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        match self {
+            Self::FreeKeyOutOfBounds(id)=>write!(f, "free key {} is out of bounds", id),
+            Self::FreeKeyInUse(id)=>write!(f, "free key {} points at an occupied or reserved slot", id),
+            Self::DuplicateFreeKey(id)=>write!(f, "free key {} is on the free list more than once", id),
+            Self::LengthMismatch{stored, actual}=>write!(f, "stored length {} does not match the {} occupied slots", stored, actual),
+        }
+    }
+}
+impl std::error::Error for InvalidSlotMap {}
+
+
+/// Decides which freed slot gets reused next. Delaying reuse makes stale keys keep pointing at
+/// empty slots for longer, so ABA bugs are more likely to show up as a `None` instead of silently
+/// aliasing new data.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FreePolicy {
+    /// Reuse the most recently freed slot first
+    #[default]
+    Lifo,
+    /// Reuse the least recently freed slot first
+    Fifo,
+    /// Like `Fifo`, but never reuse any of the last `n` freed slots. The map grows instead.
+    Quarantine(usize),
+}
+
+
+/// The raw deserialized form of a [`SlotMap`] before it has been validated
+#[derive(Deserialize)]
+struct SlotMapData<K: Key, T> {
+    inner: Vec<Slot<T>>,
+    free: VecDeque<K>,
+    /// Missing from maps serialized before the length was stored, so it is recounted then
+    #[serde(default)]
+    len: Option<usize>,
+    #[serde(default)]
+    policy: FreePolicy,
+}
+
+
 /// A simple map of key:value that reuses old keys that are removed. DOES NOT solve the ABA
-/// problem. The user (me) assumes all responsibility to ensure all keys are used properly.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// problem. The user (me) assumes all responsibility to ensure all keys are used properly. Use
+/// [`crate::gen_slotmap::GenSlotMap`] if keys need to be held across removals.
+///
+/// Deserializing validates the map (see [`SlotMap::validate`]) and rejects inconsistent data.
+///
+/// The `Debug` output only shows the occupied slots as `{key => value, ...}`. The alternate form
+/// (`{:#?}`) also shows some statistics about the slots.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(try_from = "SlotMapData<K, T>")]
 pub struct SlotMap<K: Key, T> {
     inner: Vec<Slot<T>>,
-    free: Vec<K>,
+    free: VecDeque<K>,
+    len: usize,
+    policy: FreePolicy,
+    /// One bit per slot that is set when the slot has data. Rebuilt when deserializing.
+    #[serde(skip)]
+    occupied: Bitmap,
+}
+impl<K: Key, T> TryFrom<SlotMapData<K, T>> for SlotMap<K, T> {
+    type Error = InvalidSlotMap;
+    fn try_from(data: SlotMapData<K, T>)->Result<Self, InvalidSlotMap> {
+        let len = data.len.unwrap_or_else(||data.inner.iter().filter(|s|s.has_data()).count());
+        let mut map = SlotMap {
+            inner: data.inner,
+            free: data.free,
+            len,
+            policy: data.policy,
+            occupied: Bitmap::new(),
+        };
+        map.rebuild_occupied();
+        map.validate()?;
+
+        return Ok(map);
+    }
 }
 impl<K: Key, T> SlotMap<K, T> {
     fn get_slot(&mut self)->K {
         let k;
-        if let Some(key) = self.free.pop() {
+        if let Some(key) = self.pop_free() {
+            // a free key pointing at a live or reserved slot means the free list is corrupt and we
+            // would hand out an alias of an existing key.
+            debug_assert!(
+                self.inner.get(key.id()).map(|s|!(s.has_data() || s.is_reserved())).unwrap_or(false),
+                "SlotMap free list is corrupt: free key {} points at an occupied, reserved, or missing slot",
+                key.id(),
+            );
             k = key;
+            self.inner[k.id()] = Slot::Reserved;
         } else {
             k = K::from_id(self.inner.len());
             self.inner.push(Slot::Reserved);
@@ -75,6 +200,62 @@ impl<K: Key, T> SlotMap<K, T> {
         return k;
     }
 
+    /// Pushes a key onto the free list without checking it, so tests can corrupt the free list
+    #[cfg(test)]
+    fn push_free_unchecked(&mut self, key: K) {
+        self.free.push_back(key);
+    }
+
+    /// The free key the next insert will use, according to the policy
+    fn next_free(&self)->Option<&K> {
+        match self.policy {
+            FreePolicy::Lifo=>self.free.back(),
+            FreePolicy::Fifo=>self.free.front(),
+            FreePolicy::Quarantine(n)=>if self.free.len() > n {
+                self.free.front()
+            } else {
+                None
+            },
+        }
+    }
+
+    fn pop_free(&mut self)->Option<K> {
+        self.next_free()?;
+
+        match self.policy {
+            FreePolicy::Lifo=>self.free.pop_back(),
+            _=>self.free.pop_front(),
+        }
+    }
+
+    /// Puts the data in the slot and marks it occupied
+    fn occupy(&mut self, id: usize, data: T)->&mut T {
+        self.inner[id].insert(data);
+        self.occupied.set(id);
+        self.len += 1;
+
+        return self.inner[id].as_mut().unwrap();
+    }
+
+    /// Takes the data out of the slot, if there is any, and puts the key on the free list
+    fn vacate(&mut self, id: usize)->Option<T> {
+        let data = self.inner[id].take_value()?;
+        self.occupied.unset(id);
+        self.free.push_back(K::from_id(id));
+        self.len -= 1;
+
+        return Some(data);
+    }
+
+    fn rebuild_occupied(&mut self) {
+        self.occupied.clear();
+        for (i, slot) in self.inner.iter().enumerate() {
+            if slot.has_data() {
+                self.occupied.set(i);
+            }
+        }
+    }
+
     /// if the key points to a reserved slot or some data we are storing
     fn is_key_valid(&self, k: &K)->bool {
         if k.id() < self.inner.len() {
@@ -88,17 +269,93 @@ impl<K: Key, T> SlotMap<K, T> {
     pub fn new()->Self {
         SlotMap {
             inner: Vec::new(),
-            free: Vec::new(),
+            free: VecDeque::new(),
+            len: 0,
+            policy: FreePolicy::Lifo,
+            occupied: Bitmap::new(),
+        }
+    }
+
+    /// Creates a new map that reuses freed slots according to `policy`
+    pub fn with_policy(policy: FreePolicy)->Self {
+        let mut map = Self::new();
+        map.policy = policy;
+        return map;
+    }
+
+    pub fn policy(&self)->FreePolicy {
+        self.policy
+    }
+
+    /// Changes how freed slots are reused. Slots that are already free are kept in the order they
+    /// were freed.
+    pub fn set_policy(&mut self, policy: FreePolicy) {
+        self.policy = policy;
+    }
+
+    /// Creates a new map with room for at least `capacity` slots
+    pub fn with_capacity(capacity: usize)->Self {
+        SlotMap {
+            inner: Vec::with_capacity(capacity),
+            free: VecDeque::new(),
+            len: 0,
+            policy: FreePolicy::Lifo,
+            occupied: Bitmap::with_capacity(capacity),
         }
     }
 
+    /// Makes room for at least `additional` more slots without reallocating. Free slots are not
+    /// taken into account.
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional);
+        self.occupied.reserve_bits(self.inner.len() + additional);
+    }
+
     pub fn insert(&mut self, data: T)->K {
         let key = self.get_slot();
-        self.inner[key.id()].insert(data);
+        self.occupy(key.id(), data);
 
         return key;
     }
 
+    /// Inserts the data and returns both the key and a reference to the inserted data
+    pub fn insert_and_get(&mut self, data: T)->(K, &mut T) {
+        let key = self.get_slot();
+        let data = self.occupy(key.id(), data);
+
+        return (key, data);
+    }
+
+    /// Inserts the data only if it can be done without allocating, by reusing a free slot or using
+    /// spare capacity. Returns `Err(data)` if the map would have to grow.
+    pub fn try_insert_within_capacity(&mut self, data: T)->Result<K, T> {
+        let next_id = match self.next_free() {
+            Some(key)=>key.id(),
+            None if self.inner.len() < self.inner.capacity()=>self.inner.len(),
+            None=>return Err(data),
+        };
+        if !self.occupied.can_set_without_alloc(next_id) {
+            return Err(data);
+        }
+
+        return Ok(self.insert(data));
+    }
+
+    /// Reserves a slot and gives its key to `f` to create the data, so the data can contain its
+    /// own key.
+    pub fn insert_with_key<F: FnOnce(K)->T>(&mut self, f: F)->K {
+        let key = self.get_slot();
+        let data = f(K::from_id(key.id()));
+        self.occupy(key.id(), data);
+
+        return key;
+    }
+
+    /// Inserts all the data and returns the keys in the same order
+    pub fn extend_with_keys<I: IntoIterator<Item = T>>(&mut self, iter: I)->Vec<K> {
+        iter.into_iter().map(|data|self.insert(data)).collect()
+    }
+
     #[inline]
     pub fn reserve_slot(&mut self)->K {
         self.get_slot()
@@ -113,11 +370,57 @@ impl<K: Key, T> SlotMap<K, T> {
             return Err(data);
         }
 
-        self.inner[key.id()].insert(data);
+        self.occupy(key.id(), data);
 
         return Ok(());
     }
 
+    /// Gives a reserved slot back to the free list without filling it. Returns `false` if the key
+    /// does not point at a reserved slot.
+    pub fn cancel_reserved(&mut self, key: K)->bool {
+        if !self.is_reserved(K::from_id(key.id())) {return false}
+
+        self.inner[key.id()] = Slot::None;
+        self.free.push_back(key);
+
+        return true;
+    }
+
+    /// The number of slots that are reserved, but not filled yet. This is a full scan, so it's
+    /// mostly useful to check for leaked reservations.
+    pub fn reserved_count(&self)->usize {
+        self.inner.iter().filter(|s|s.is_reserved()).count()
+    }
+
+    /// Iterate over the keys of all reserved slots
+    pub fn iter_reserved<'a>(&'a self)->SlotMapReserved<'a, K, T> {
+        SlotMapReserved {
+            inner: self.inner.iter().enumerate(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Get the entry for the key to insert or modify the data in place with only one validity
+    /// check. Returns `None` if the key is past the end of the slots, so a bad key can't make the
+    /// map grow.
+    pub fn entry<'a>(&'a mut self, key: K)->Option<Entry<'a, K, T>> {
+        return Some(match self.inner.get(key.id())? {
+            Slot::Value(_)=>Entry::Occupied(OccupiedEntry {map: self, key}),
+            Slot::Reserved=>Entry::Reserved(ReservedEntry {map: self, key}),
+            Slot::None=>Entry::Vacant(VacantEntry {map: self, key}),
+        });
+    }
+
+    /// If the key points at some data. Reserved slots don't count.
+    pub fn contains_key(&self, key: K)->bool {
+        self.inner.get(key.id()).map(Slot::has_data).unwrap_or(false)
+    }
+
+    /// If the key points at a reserved slot that has not been filled yet
+    pub fn is_reserved(&self, key: K)->bool {
+        self.inner.get(key.id()).map(Slot::is_reserved).unwrap_or(false)
+    }
+
     pub fn get(&self, key: K)->Option<&T> {
         if !self.is_key_valid(&key) {return None}
 
@@ -132,11 +435,219 @@ impl<K: Key, T> SlotMap<K, T> {
         return self.inner[id].as_mut();
     }
 
+    /// Get the data without checking the key.
+    ///
+    /// # Safety
+    /// The key must point at an occupied slot of this map.
+    pub unsafe fn get_unchecked(&self, key: K)->&T {
+        match unsafe {self.inner.get_unchecked(key.id())} {
+            Slot::Value(t)=>t,
+            _=>unsafe {std::hint::unreachable_unchecked()},
+        }
+    }
+
+    /// Get the data mutably without checking the key.
+    ///
+    /// # Safety
+    /// The key must point at an occupied slot of this map.
+    pub unsafe fn get_unchecked_mut(&mut self, key: K)->&mut T {
+        match unsafe {self.inner.get_unchecked_mut(key.id())} {
+            Slot::Value(t)=>t,
+            _=>unsafe {std::hint::unreachable_unchecked()},
+        }
+    }
+
+    /// Get mutable references to the data of several keys at once. Returns `None` if any of the
+    /// keys are equal or don't point at data.
+    pub fn get_disjoint_mut<const N: usize>(&mut self, keys: [K; N])->Option<[&mut T; N]> {
+        if !keys.iter().all(|k|self.contains_key(K::from_id(k.id()))) {return None}
+
+        let slots = self.inner.get_disjoint_mut(keys.map(|k|k.id())).ok()?;
+
+        return Some(slots.map(|slot|slot.as_mut().unwrap()));
+    }
+
+    /// Removes the data and returns the key to the free list. Reserved slots are left untouched.
     pub fn remove(&mut self, key: K)->Option<T> {
         if !self.is_key_valid(&key) {return None}
 
-        let id = key.id();
-        return self.inner[id].take();
+        return self.vacate(key.id());
+    }
+
+    /// Removes all the data that does not match the predicate and frees their keys
+    pub fn retain<F: FnMut(K, &mut T)->bool>(&mut self, mut f: F) {
+        let mut next = self.occupied.next_set(0);
+        while let Some(i) = next {
+            let keep = f(K::from_id(i), self.inner[i].as_mut().unwrap());
+            if !keep {
+                self.vacate(i);
+            }
+
+            next = self.occupied.next_set(i + 1);
+        }
+    }
+
+    /// Moves all data and reserved slots down to fill the empty slots, then shrinks the storage.
+    /// `remap` is called with `(old_key, new_key)` for every slot that moved, so references stored
+    /// elsewhere can be fixed up. Keys that are not reported stay the same.
+    pub fn compact<F: FnMut(K, K)>(&mut self, mut remap: F) {
+        let mut next = 0;
+        for i in 0..self.inner.len() {
+            if !(self.inner[i].has_data() || self.inner[i].is_reserved()) {continue}
+
+            if i != next {
+                self.inner.swap(i, next);
+                remap(K::from_id(i), K::from_id(next));
+            }
+            next += 1;
+        }
+
+        self.inner.truncate(next);
+        self.inner.shrink_to_fit();
+        self.free.clear();
+        self.rebuild_occupied();
+        self.occupied.shrink_to_fit();
+    }
+
+    /// Removes all the data and slots, but keeps the allocation around. All previously issued
+    /// keys, including reserved ones, become invalid.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        self.free.clear();
+        self.occupied.clear();
+        self.len = 0;
+    }
+
+    /// Removes and yields all the data. Once the iterator is dropped the map is empty and every
+    /// non-reserved slot is on the free list. Reserved slots stay reserved.
+    pub fn drain<'a>(&'a mut self)->SlotMapDrain<'a, K, T> {
+        self.free.clear();
+        self.occupied.clear();
+        self.len = 0;
+
+        SlotMapDrain {
+            inner: self.inner.iter_mut().enumerate(),
+            free: &mut self.free,
+        }
+    }
+
+    /// The number of occupied slots. Reserved slots are not counted.
+    pub fn len(&self)->usize {
+        self.len
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.len == 0
+    }
+
+    /// The total number of slots, including empty and reserved ones
+    pub fn slot_count(&self)->usize {
+        self.inner.len()
+    }
+
+    /// The number of slots that can be held without reallocating
+    pub fn capacity(&self)->usize {
+        self.inner.capacity()
+    }
+
+    /// Removes the free slots at the end of the map, drops them from the free list, and releases
+    /// unused capacity in the backing storage. Occupied and reserved slots are never touched, so
+    /// every live key stays valid and [`Self::len`] stays the same. Returns `true` if anything
+    /// actually shrank.
+    pub fn shrink_to_fit(&mut self)->bool {
+        let old = (self.inner.len(), self.inner.capacity(), self.free.capacity());
+
+        let mut new_len = self.inner.len();
+        while new_len > 0 && !(self.inner[new_len - 1].has_data() || self.inner[new_len - 1].is_reserved()) {
+            new_len -= 1;
+        }
+        if new_len < self.inner.len() {
+            self.inner.truncate(new_len);
+            self.free.retain(|k|k.id() < new_len);
+        }
+
+        self.inner.shrink_to_fit();
+        self.free.shrink_to_fit();
+        self.occupied.shrink_to_fit();
+
+        return (self.inner.len(), self.inner.capacity(), self.free.capacity()) != old;
+    }
+
+    /// Iterate over all `(key, value)` pairs. Skips empty and reserved slots.
+    pub fn iter<'a>(&'a self)->SlotMapIter<'a, K, T> {
+        SlotMapIter {
+            inner: self.inner.iter(),
+            occupied: &self.occupied,
+            pos: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Iterate over all `(key, value)` pairs mutably. Skips empty and reserved slots.
+    pub fn iter_mut<'a>(&'a mut self)->SlotMapIterMut<'a, K, T> {
+        SlotMapIterMut {
+            inner: self.inner.iter_mut(),
+            occupied: &self.occupied,
+            pos: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Iterate over the keys of all occupied slots
+    pub fn keys<'a>(&'a self)->SlotMapKeys<'a, K, T> {
+        SlotMapKeys(self.iter())
+    }
+
+    pub fn values<'a>(&'a self)->SlotMapValues<'a, K, T> {
+        SlotMapValues(self.iter())
+    }
+
+    pub fn values_mut<'a>(&'a mut self)->SlotMapValuesMut<'a, K, T> {
+        SlotMapValuesMut(self.iter_mut())
+    }
+
+    /// Checks that the free list and length agree with the slots. Every method on the map keeps
+    /// these invariants, so this only fails for hand-edited or corrupted data.
+    pub fn validate(&self)->Result<(), InvalidSlotMap> {
+        let mut seen = vec![false; self.inner.len()];
+        for key in self.free.iter() {
+            let id = key.id();
+            let Some(slot) = self.inner.get(id) else {
+                return Err(InvalidSlotMap::FreeKeyOutOfBounds(id));
+            };
+            if slot.has_data() || slot.is_reserved() {
+                return Err(InvalidSlotMap::FreeKeyInUse(id));
+            }
+            if seen[id] {
+                return Err(InvalidSlotMap::DuplicateFreeKey(id));
+            }
+            seen[id] = true;
+        }
+
+        let actual = self.inner.iter().filter(|s|s.has_data()).count();
+        if actual != self.len {
+            return Err(InvalidSlotMap::LengthMismatch {
+                stored: self.len,
+                actual,
+            });
+        }
+
+        return Ok(());
+    }
+
+    /// Finds the key of the first data that matches the predicate
+    pub fn find_key<F: FnMut(&T)->bool>(&self, mut f: F)->Option<K> {
+        self.iter().find(|(_, t)|f(t)).map(|(k, _)|k)
+    }
+
+    /// Finds the key of the first data equal to `data`
+    pub fn position_of(&self, data: &T)->Option<K> where T: PartialEq {
+        self.find_key(|t|t == data)
+    }
+
+    /// Finds the first occupied slot after `key`, skipping empty slots in bulk
+    pub fn next_occupied_after(&self, key: K)->Option<K> {
+        self.occupied.next_set(key.id().checked_add(1)?).map(K::from_id)
     }
 
     pub fn key_of_last_item(&self)->Option<K> {
@@ -160,3 +671,364 @@ impl<K: Key, T> IndexMut<K> for SlotMap<K, T> {
         self.get_mut(key).unwrap()
     }
 }
+impl<K: Key + Debug, T: Debug> Debug for SlotMap<K, T> {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        let entries = DebugEntries(self);
+
+        if f.alternate() {
+            f.debug_struct("SlotMap")
+                .field("len", &self.len)
+                .field("slot_count", &self.inner.len())
+                .field("reserved", &self.reserved_count())
+                .field("free", &self.free.len())
+                .field("capacity", &self.inner.capacity())
+                .field("policy", &self.policy)
+                .field("entries", &entries)
+                .finish()
+        } else {
+            entries.fmt(f)
+        }
+    }
+}
+/// Two maps are equal if they have data at the same keys and the data is equal. The free list,
+/// reserved slots, policy and capacity are ignored.
+impl<K: Key, T: PartialEq> PartialEq for SlotMap<K, T> {
+    fn eq(&self, other: &Self)->bool {
+        self.len == other.len && self.iter()
+            .zip(other.iter())
+            .all(|((k1, t1), (k2, t2))|k1.id() == k2.id() && t1 == t2)
+    }
+}
+impl<K: Key, T: Eq> Eq for SlotMap<K, T> {}
+impl<K: Key, T> FromIterator<T> for SlotMap<K, T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I)->Self {
+        let mut map = SlotMap::new();
+        map.extend(iter);
+        return map;
+    }
+}
+impl<K: Key, T> Extend<T> for SlotMap<K, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0.saturating_sub(self.free.len()));
+
+        for data in iter {
+            self.insert(data);
+        }
+    }
+}
+impl<K: Key, T> IntoIterator for SlotMap<K, T> {
+    type Item = (K, T);
+    type IntoIter = SlotMapIntoIter<K, T>;
+    fn into_iter(self)->Self::IntoIter {
+        SlotMapIntoIter {
+            inner: self.inner.into_iter(),
+            occupied: self.occupied,
+            pos: 0,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+struct DebugEntries<'a, K: Key, T>(&'a SlotMap<K, T>);
+impl<'a, K: Key + Debug, T: Debug> Debug for DebugEntries<'a, K, T> {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        f.debug_set()
+            .entries(self.0.iter().map(|(k, t)|DebugEntry(k, t)))
+            .finish()
+    }
+}
+
+struct DebugEntry<'a, K, T>(K, &'a T);
+impl<'a, K: Debug, T: Debug> Debug for DebugEntry<'a, K, T> {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        self.0.fmt(f)?;
+        f.write_str(" => ")?;
+        self.1.fmt(f)
+    }
+}
+
+/// The iterators use the occupancy bitmap to find the next occupied slot, then skip straight to it
+/// with `nth`. `pos` is the id of the slot `inner` yields next.
+pub struct SlotMapIter<'a, K: Key, T: 'a> {
+    inner: Iter<'a, Slot<T>>,
+    occupied: &'a Bitmap,
+    pos: usize,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, T: 'a> Iterator for SlotMapIter<'a, K, T> {
+    type Item = (K, &'a T);
+    fn next(&mut self)->Option<Self::Item> {
+        let id = self.occupied.next_set(self.pos)?;
+        let slot = self.inner.nth(id - self.pos)?;
+        self.pos = id + 1;
+
+        return Some((K::from_id(id), slot.as_ref().unwrap()));
+    }
+}
+
+pub struct SlotMapIterMut<'a, K: Key, T: 'a> {
+    inner: IterMut<'a, Slot<T>>,
+    occupied: &'a Bitmap,
+    pos: usize,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, T: 'a> Iterator for SlotMapIterMut<'a, K, T> {
+    type Item = (K, &'a mut T);
+    fn next(&mut self)->Option<Self::Item> {
+        let id = self.occupied.next_set(self.pos)?;
+        let slot = self.inner.nth(id - self.pos)?;
+        self.pos = id + 1;
+
+        return Some((K::from_id(id), slot.as_mut().unwrap()));
+    }
+}
+
+pub struct SlotMapKeys<'a, K: Key, T: 'a>(SlotMapIter<'a, K, T>);
+impl<'a, K: Key, T: 'a> Iterator for SlotMapKeys<'a, K, T> {
+    type Item = K;
+    fn next(&mut self)->Option<K> {
+        self.0.next().map(|(k, _)|k)
+    }
+}
+
+pub struct SlotMapValues<'a, K: Key, T: 'a>(SlotMapIter<'a, K, T>);
+impl<'a, K: Key, T: 'a> Iterator for SlotMapValues<'a, K, T> {
+    type Item = &'a T;
+    fn next(&mut self)->Option<&'a T> {
+        self.0.next().map(|(_, t)|t)
+    }
+}
+
+pub struct SlotMapValuesMut<'a, K: Key, T: 'a>(SlotMapIterMut<'a, K, T>);
+impl<'a, K: Key, T: 'a> Iterator for SlotMapValuesMut<'a, K, T> {
+    type Item = &'a mut T;
+    fn next(&mut self)->Option<&'a mut T> {
+        self.0.next().map(|(_, t)|t)
+    }
+}
+
+pub struct SlotMapIntoIter<K: Key, T> {
+    inner: std::vec::IntoIter<Slot<T>>,
+    occupied: Bitmap,
+    pos: usize,
+    _phantom: PhantomData<K>,
+}
+impl<K: Key, T> Iterator for SlotMapIntoIter<K, T> {
+    type Item = (K, T);
+    fn next(&mut self)->Option<Self::Item> {
+        let id = self.occupied.next_set(self.pos)?;
+        let mut slot = self.inner.nth(id - self.pos)?;
+        self.pos = id + 1;
+
+        return Some((K::from_id(id), slot.take().unwrap()));
+    }
+}
+
+pub struct SlotMapReserved<'a, K: Key, T: 'a> {
+    inner: Enumerate<Iter<'a, Slot<T>>>,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, T: 'a> Iterator for SlotMapReserved<'a, K, T> {
+    type Item = K;
+    fn next(&mut self)->Option<K> {
+        while let Some((i, slot)) = self.inner.next() {
+            if slot.is_reserved() {
+                return Some(K::from_id(i));
+            }
+        }
+
+        return None;
+    }
+}
+
+pub struct SlotMapDrain<'a, K: Key, T: 'a> {
+    inner: Enumerate<IterMut<'a, Slot<T>>>,
+    free: &'a mut VecDeque<K>,
+}
+impl<'a, K: Key, T: 'a> Iterator for SlotMapDrain<'a, K, T> {
+    type Item = (K, T);
+    fn next(&mut self)->Option<Self::Item> {
+        while let Some((i, slot)) = self.inner.next() {
+            if slot.is_reserved() {continue}
+
+            self.free.push_back(K::from_id(i));
+            if let Some(t) = slot.take() {
+                return Some((K::from_id(i), t));
+            }
+        }
+
+        return None;
+    }
+}
+impl<'a, K: Key, T: 'a> Drop for SlotMapDrain<'a, K, T> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+
+/// A view into a single slot of a [`SlotMap`]
+pub enum Entry<'a, K: Key, T> {
+    Occupied(OccupiedEntry<'a, K, T>),
+    Reserved(ReservedEntry<'a, K, T>),
+    Vacant(VacantEntry<'a, K, T>),
+}
+impl<'a, K: Key, T> Entry<'a, K, T> {
+    pub fn key(&self)->&K {
+        match self {
+            Self::Occupied(e)=>e.key(),
+            Self::Reserved(e)=>e.key(),
+            Self::Vacant(e)=>e.key(),
+        }
+    }
+
+    /// Inserts `default` if the slot is reserved or vacant and returns a reference to the data
+    pub fn or_insert(self, default: T)->&'a mut T {
+        self.or_insert_with(||default)
+    }
+
+    /// Inserts the result of `f` if the slot is reserved or vacant and returns a reference to the
+    /// data
+    pub fn or_insert_with<F: FnOnce()->T>(self, f: F)->&'a mut T {
+        match self {
+            Self::Occupied(e)=>e.into_mut(),
+            Self::Reserved(e)=>e.insert(f()),
+            Self::Vacant(e)=>e.insert(f()),
+        }
+    }
+
+    /// Modifies the data if the slot is occupied
+    pub fn and_modify<F: FnOnce(&mut T)>(mut self, f: F)->Self {
+        if let Self::Occupied(e) = &mut self {
+            f(e.get_mut());
+        }
+
+        return self;
+    }
+}
+
+pub struct OccupiedEntry<'a, K: Key, T> {
+    map: &'a mut SlotMap<K, T>,
+    key: K,
+}
+impl<'a, K: Key, T> OccupiedEntry<'a, K, T> {
+    pub fn key(&self)->&K {
+        &self.key
+    }
+
+    pub fn get(&self)->&T {
+        self.map.inner[self.key.id()].as_ref().unwrap()
+    }
+
+    pub fn get_mut(&mut self)->&mut T {
+        self.map.inner[self.key.id()].as_mut().unwrap()
+    }
+
+    pub fn into_mut(self)->&'a mut T {
+        self.map.inner[self.key.id()].as_mut().unwrap()
+    }
+
+    /// Replaces the data, returning the old data
+    pub fn insert(&mut self, data: T)->T {
+        std::mem::replace(self.get_mut(), data)
+    }
+
+    pub fn remove(self)->T {
+        self.map.remove(self.key).unwrap()
+    }
+}
+
+pub struct ReservedEntry<'a, K: Key, T> {
+    map: &'a mut SlotMap<K, T>,
+    key: K,
+}
+impl<'a, K: Key, T> ReservedEntry<'a, K, T> {
+    pub fn key(&self)->&K {
+        &self.key
+    }
+
+    pub fn insert(self, data: T)->&'a mut T {
+        return self.map.occupy(self.key.id(), data);
+    }
+}
+
+/// A key that points at an empty slot. Inserting claims the slot and takes it off the free list.
+pub struct VacantEntry<'a, K: Key, T> {
+    map: &'a mut SlotMap<K, T>,
+    key: K,
+}
+impl<'a, K: Key, T> VacantEntry<'a, K, T> {
+    pub fn key(&self)->&K {
+        &self.key
+    }
+
+    pub fn insert(self, data: T)->&'a mut T {
+        let id = self.key.id();
+        let map = self.map;
+
+        if let Some(i) = map.free.iter().position(|k|k.id() == id) {
+            map.free.remove(i);
+        }
+
+        return map.occupy(id, data);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_keys_are_reused() {
+        let mut map = SlotMap::<usize, &str>::new();
+        let a = map.insert("a");
+        map.insert("b");
+
+        assert_eq!(map.remove(a), Some("a"));
+        assert_eq!(map.insert("c"), a);
+        assert_eq!(map[a], "c");
+    }
+
+    #[test]
+    fn shrink_to_fit_keeps_data() {
+        let mut map = SlotMap::<usize, i32>::new();
+        let a = map.insert(1);
+        let b = map.insert(2);
+        let c = map.insert(3);
+        map.remove(b);
+        map.remove(c);
+
+        assert!(map.shrink_to_fit());
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.slot_count(), 1);
+        assert_eq!(map.get(a), Some(&1));
+
+        assert!(!map.shrink_to_fit());
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.slot_count(), 1);
+    }
+
+    #[test]
+    fn next_occupied_after_invalid_key_is_none() {
+        crate::define_keys!(TestKey,);
+
+        let mut map = SlotMap::<TestKey, i32>::new();
+        map.insert(1);
+
+        assert_eq!(map.next_occupied_after(TestKey::invalid()), None);
+    }
+
+    /// The free list check is a `debug_assert!`, so this only panics with debug assertions on
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "SlotMap free list is corrupt")]
+    fn corrupt_free_list_panics() {
+        let mut map = SlotMap::<usize, &str>::new();
+        let a = map.insert("a");
+        map.push_free_unchecked(a);
+
+        map.insert("b");
+    }
+}
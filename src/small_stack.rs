@@ -0,0 +1,463 @@
+use std::{
+    fmt::{
+        self,
+        Debug,
+        Formatter,
+    },
+    ops::{
+        Index,
+        IndexMut,
+    },
+    iter::Rev,
+    vec::{
+        Drain,
+        IntoIter,
+    },
+};
+use crate::{
+    array_stack::{
+        ArrayStack,
+        ArrayStackIntoIter,
+    },
+    stack::{
+        StackOverflow,
+        StackUnderflow,
+        StackError,
+    },
+};
+
+
+#[derive(Clone)]
+enum Storage<T, const N: usize> {
+    Inline(ArrayStack<T, N>),
+    Heap(Vec<T>),
+}
+
+
+/// A [`crate::stack::Stack`] that stores up to `N` items inline and only allocates once it grows
+/// past that. After moving to the heap it stays there, even if items are popped or the stack is
+/// cleared.
+#[derive(Clone)]
+pub struct SmallStack<T, const N: usize> {
+    storage: Storage<T, N>,
+}
+impl<T, const N: usize> SmallStack<T, N> {
+    /// Creates a new stack
+    pub fn new()->Self {
+        SmallStack {
+            storage: Storage::Inline(ArrayStack::new()),
+        }
+    }
+
+    /// Clears the stack
+    pub fn clear(&mut self) {
+        match &mut self.storage {
+            Storage::Inline(items)=>items.clear(),
+            Storage::Heap(items)=>items.clear(),
+        }
+    }
+
+    /// Pushes the item onto the stack, moving the items to the heap if the inline storage is full
+    pub fn push(&mut self, item: T) {
+        match &mut self.storage {
+            Storage::Inline(items)=>if let Err(StackOverflow {item, ..}) = items.try_push(item) {
+                let mut heap = Vec::with_capacity((N * 2).max(4));
+                while let Some(t) = items.pop() {
+                    heap.push(t);
+                }
+                heap.reverse();
+                heap.push(item);
+
+                self.storage = Storage::Heap(heap);
+            },
+            Storage::Heap(items)=>items.push(item),
+        }
+    }
+
+    pub fn pop(&mut self)->Option<T> {
+        match &mut self.storage {
+            Storage::Inline(items)=>items.pop(),
+            Storage::Heap(items)=>items.pop(),
+        }
+    }
+
+    /// Pops the top `n` items, with the top of the stack last (the order they were pushed in).
+    /// Returns `None` and leaves the stack alone if there are fewer than `n` items.
+    pub fn pop_n(&mut self, n: usize)->Option<Vec<T>> {
+        Some(self.drain_top(n)?.collect())
+    }
+
+    /// Same as [`Self::pop_n`], but returns an array
+    pub fn pop_array<const M: usize>(&mut self)->Option<[T; M]> {
+        let mut top = self.drain_top(M)?;
+        return Some(std::array::from_fn(|_|top.next().unwrap()));
+    }
+
+    /// Removes the top `n` items, yielding them in the order they were pushed. Returns `None` and
+    /// leaves the stack alone if there are fewer than `n` items.
+    pub fn drain_top(&mut self, n: usize)->Option<SmallStackDrain<'_, T, N>> {
+        let inner = match &mut self.storage {
+            Storage::Inline(items)=>DrainInner::Inline(items.drain_top(n)?),
+            Storage::Heap(items)=>{
+                let start = items.len().checked_sub(n)?;
+                DrainInner::Heap(items.drain(start..))
+            },
+        };
+
+        return Some(SmallStackDrain {inner});
+    }
+
+    /// Pops items until there are at most `len` left
+    pub fn truncate_to(&mut self, len: usize) {
+        match &mut self.storage {
+            Storage::Inline(items)=>items.truncate_to(len),
+            Storage::Heap(items)=>items.truncate(len),
+        }
+    }
+
+    /// Moves the top `n` items into a new stack, keeping their order. Returns `None` and leaves
+    /// the stack alone if there are fewer than `n` items.
+    pub fn split_off_top(&mut self, n: usize)->Option<Self> {
+        let mut top = Self::new();
+        top.extend(self.drain_top(n)?);
+        return Some(top);
+    }
+
+    fn check_len(&self, needed: usize)->Result<(), StackUnderflow> {
+        if self.len() < needed {
+            return Err(StackUnderflow {
+                needed,
+                len: self.len(),
+            });
+        }
+
+        return Ok(());
+    }
+
+    /// Pushes a copy of the top item: `a -> a a`
+    pub fn dup(&mut self)->Result<(), StackError> where T: Clone {
+        self.pick(0)
+    }
+
+    /// Swaps the top two items: `a b -> b a`
+    pub fn swap(&mut self)->Result<(), StackUnderflow> {
+        self.check_len(2)?;
+        let len = self.len();
+        self.as_mut_slice().swap(len - 1, len - 2);
+
+        return Ok(());
+    }
+
+    /// Pushes a copy of the second item: `a b -> a b a`
+    pub fn over(&mut self)->Result<(), StackError> where T: Clone {
+        self.pick(1)
+    }
+
+    /// Moves the third item to the top: `a b c -> b c a`
+    pub fn rot(&mut self)->Result<(), StackUnderflow> {
+        self.check_len(3)?;
+        let len = self.len();
+        self.as_mut_slice()[len - 3..].rotate_left(1);
+
+        return Ok(());
+    }
+
+    /// Pushes a copy of the item `depth` items down from the top. `pick(0)` is the same as
+    /// [`Self::dup`] and `pick(1)` is the same as [`Self::over`]. Returns [`StackError`] to match
+    /// [`crate::stack::Stack::pick`], but since the stack has no limit it can only underflow.
+    pub fn pick(&mut self, depth: usize)->Result<(), StackError> where T: Clone {
+        self.check_len(depth.saturating_add(1))?;
+        let item = self.as_slice()[self.len() - depth - 1].clone();
+        self.push(item);
+
+        return Ok(());
+    }
+
+    /// Removes all the items that don't match the predicate, keeping the order of the rest
+    pub fn retain<F: FnMut(&T)->bool>(&mut self, mut f: F) {
+        match &mut self.storage {
+            Storage::Inline(items)=>{
+                // yields from the bottom up, so the kept items are pushed back in order
+                for item in std::mem::take(items).into_iter().rev() {
+                    if f(&item) {
+                        items.push(item);
+                    }
+                }
+            },
+            Storage::Heap(items)=>items.retain(f),
+        }
+    }
+
+    pub fn last(&self)->Option<&T> {
+        self.as_slice().last()
+    }
+
+    pub fn last_mut(&mut self)->Option<&mut T> {
+        self.as_mut_slice().last_mut()
+    }
+
+    /// Gets the item `depth` items down from the top, where `0` is the top. Returns `None` past
+    /// the bottom of the stack.
+    pub fn get(&self, depth: usize)->Option<&T> {
+        let index = self.len().checked_sub(depth)?.checked_sub(1)?;
+        return self.as_slice().get(index);
+    }
+
+    pub fn get_mut(&mut self, depth: usize)->Option<&mut T> {
+        let index = self.len().checked_sub(depth)?.checked_sub(1)?;
+        return self.as_mut_slice().get_mut(index);
+    }
+
+    /// The top `n` items, with the top of the stack last. Returns `None` if there are fewer than
+    /// `n` items.
+    pub fn peek_n(&self, n: usize)->Option<&[T]> {
+        let start = self.len().checked_sub(n)?;
+        return Some(&self.as_slice()[start..]);
+    }
+
+    pub fn contains(&self, item: &T)->bool where T: PartialEq {
+        self.as_slice().contains(item)
+    }
+
+    /// The depth from the top of the nearest item matching the predicate
+    pub fn position<F: FnMut(&T)->bool>(&self, f: F)->Option<usize> {
+        self.iter().position(f)
+    }
+
+    /// The nearest item to the top matching the predicate
+    pub fn find<F: FnMut(&T)->bool>(&self, mut f: F)->Option<&T> {
+        self.iter().find(|t|f(t))
+    }
+
+    /// All the items, with the bottom of the stack first
+    pub fn as_slice(&self)->&[T] {
+        match &self.storage {
+            Storage::Inline(items)=>items.as_slice(),
+            Storage::Heap(items)=>items,
+        }
+    }
+
+    pub fn as_mut_slice(&mut self)->&mut [T] {
+        match &mut self.storage {
+            Storage::Inline(items)=>items.as_mut_slice(),
+            Storage::Heap(items)=>items,
+        }
+    }
+
+    /// Iterate from the bottom of the stack to the top, in the order the items were pushed.
+    pub fn iter_bottom_up<'a>(&'a self)->std::slice::Iter<'a, T> {
+        self.as_slice().iter()
+    }
+
+    /// Iterate from the top of the stack to the bottom.
+    pub fn iter<'a>(&'a self)->impl 'a + Iterator<Item = &'a T> {
+        self.as_slice().iter().rev()
+    }
+
+    /// Iterate from the top of the stack to the bottom, mutably.
+    pub fn iter_mut<'a>(&'a mut self)->impl 'a + Iterator<Item = &'a mut T> {
+        self.as_mut_slice().iter_mut().rev()
+    }
+
+    pub fn len(&self)->usize {
+        self.as_slice().len()
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.len() == 0
+    }
+
+    /// How many items fit before the next allocation. This is `N` until the items move to the heap.
+    pub fn capacity(&self)->usize {
+        match &self.storage {
+            Storage::Inline(_)=>N,
+            Storage::Heap(items)=>items.capacity(),
+        }
+    }
+
+    /// The items with the bottom of the stack first
+    pub fn into_vec(self)->Vec<T> {
+        match self.storage {
+            Storage::Inline(items)=>items.into_iter().rev().collect(),
+            Storage::Heap(items)=>items,
+        }
+    }
+
+    /// If the items have been moved to the heap
+    pub fn is_spilled(&self)->bool {
+        matches!(self.storage, Storage::Heap(_))
+    }
+}
+impl<T, const N: usize> Default for SmallStack<T, N> {
+    fn default()->Self {
+        Self::new()
+    }
+}
+/// Shows the items from the bottom of the stack to the top
+impl<T: Debug, const N: usize> Debug for SmallStack<T, N> {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        f.debug_list()
+            .entries(self.as_slice())
+            .finish()
+    }
+}
+impl<T: PartialEq, const N: usize> PartialEq for SmallStack<T, N> {
+    fn eq(&self, other: &Self)->bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+/// Yields the items from the top of the stack to the bottom
+impl<T, const N: usize> IntoIterator for SmallStack<T, N> {
+    type Item = T;
+    type IntoIter = SmallStackIntoIter<T, N>;
+    fn into_iter(self)->Self::IntoIter {
+        let inner = match self.storage {
+            Storage::Inline(items)=>IntoIterInner::Inline(items.into_iter()),
+            Storage::Heap(items)=>IntoIterInner::Heap(items.into_iter().rev()),
+        };
+
+        return SmallStackIntoIter {inner};
+    }
+}
+impl<T, const N: usize> Extend<T> for SmallStack<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+/// Pushes the items in order, so the last one ends up on top
+impl<T, const N: usize> FromIterator<T> for SmallStack<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I)->Self {
+        let mut stack = Self::new();
+        stack.extend(iter);
+        return stack;
+    }
+}
+impl<T, const N: usize> Index<usize> for SmallStack<T, N> {
+    type Output = T;
+    fn index(&self, index: usize)->&T {
+        self.get(index).expect("SmallStack index is past the bottom of the stack")
+    }
+}
+impl<T, const N: usize> IndexMut<usize> for SmallStack<T, N> {
+    fn index_mut(&mut self, index: usize)->&mut T {
+        self.get_mut(index).expect("SmallStack index is past the bottom of the stack")
+    }
+}
+
+
+enum IntoIterInner<T, const N: usize> {
+    Inline(ArrayStackIntoIter<T, N>),
+    Heap(Rev<IntoIter<T>>),
+}
+
+/// Yields the items of a [`SmallStack`] from the top to the bottom
+pub struct SmallStackIntoIter<T, const N: usize> {
+    inner: IntoIterInner<T, N>,
+}
+impl<T, const N: usize> Iterator for SmallStackIntoIter<T, N> {
+    type Item = T;
+    fn next(&mut self)->Option<T> {
+        match &mut self.inner {
+            IntoIterInner::Inline(iter)=>iter.next(),
+            IntoIterInner::Heap(iter)=>iter.next(),
+        }
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        match &self.inner {
+            IntoIterInner::Inline(iter)=>iter.size_hint(),
+            IntoIterInner::Heap(iter)=>iter.size_hint(),
+        }
+    }
+}
+impl<T, const N: usize> DoubleEndedIterator for SmallStackIntoIter<T, N> {
+    fn next_back(&mut self)->Option<T> {
+        match &mut self.inner {
+            IntoIterInner::Inline(iter)=>iter.next_back(),
+            IntoIterInner::Heap(iter)=>iter.next_back(),
+        }
+    }
+}
+impl<T, const N: usize> ExactSizeIterator for SmallStackIntoIter<T, N> {}
+
+enum DrainInner<'a, T, const N: usize> {
+    Inline(Rev<ArrayStackIntoIter<T, N>>),
+    Heap(Drain<'a, T>),
+}
+
+/// Yields the top items of a [`SmallStack`] in the order they were pushed. See
+/// [`SmallStack::drain_top`].
+pub struct SmallStackDrain<'a, T, const N: usize> {
+    inner: DrainInner<'a, T, N>,
+}
+impl<'a, T, const N: usize> Iterator for SmallStackDrain<'a, T, N> {
+    type Item = T;
+    fn next(&mut self)->Option<T> {
+        match &mut self.inner {
+            DrainInner::Inline(iter)=>iter.next(),
+            DrainInner::Heap(iter)=>iter.next(),
+        }
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        match &self.inner {
+            DrainInner::Inline(iter)=>iter.size_hint(),
+            DrainInner::Heap(iter)=>iter.size_hint(),
+        }
+    }
+}
+impl<'a, T, const N: usize> DoubleEndedIterator for SmallStackDrain<'a, T, N> {
+    fn next_back(&mut self)->Option<T> {
+        match &mut self.inner {
+            DrainInner::Inline(iter)=>iter.next_back(),
+            DrainInner::Heap(iter)=>iter.next_back(),
+        }
+    }
+}
+impl<'a, T, const N: usize> ExactSizeIterator for SmallStackDrain<'a, T, N> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_past_the_bottom_is_none() {
+        let mut stack = SmallStack::<i32, 2>::new();
+        stack.push(1);
+
+        assert_eq!(stack.get(0), Some(&1));
+        assert_eq!(stack.get(1), None);
+        assert_eq!(stack.get(usize::MAX), None);
+        assert_eq!(stack.get_mut(usize::MAX), None);
+    }
+
+    #[test]
+    fn same_results_inline_and_spilled() {
+        for spill in [false, true] {
+            let mut stack = SmallStack::<i32, 4>::from_iter([1, 2, 3]);
+            if spill {
+                stack.extend([4, 5]);
+                stack.truncate_to(3);
+            }
+            assert_eq!(stack.is_spilled(), spill);
+
+            stack.rot().unwrap();
+            stack.swap().unwrap();
+            stack.over().unwrap();
+            assert_eq!(stack.as_slice(), [2, 1, 3, 1]);
+            assert_eq!(
+                stack.pick(9),
+                Err(StackError::Underflow(StackUnderflow {needed: 10, len: 4})),
+            );
+
+            stack.retain(|t|*t != 3);
+            assert_eq!(stack.as_slice(), [2, 1, 1]);
+            assert_eq!(stack.split_off_top(2).unwrap().into_vec(), [1, 1]);
+            assert_eq!(stack.pop_array::<1>(), Some([2]));
+            assert!(stack.pop_n(1).is_none());
+            assert_eq!(stack.clone().into_iter().count(), 0);
+        }
+    }
+}
@@ -3,10 +3,17 @@
 //! recursive AST. It is adapted to use the [`Key`] trait I have made.
 
 
+use serde::{
+    Serialize,
+    Serializer,
+    Deserialize,
+    Deserializer,
+};
 use std::{
     ops::{
         Index,
         IndexMut,
+        Deref,
     },
     slice::{
         Iter,
@@ -14,23 +21,33 @@ use std::{
     },
     iter::{
         Enumerate,
+        DoubleEndedIterator,
+        ExactSizeIterator,
         IntoIterator,
+        FromIterator,
         Extend,
     },
     marker::PhantomData,
+    vec::Drain,
+};
+use crate::{
+    Key,
+    KeyOffset,
 };
-use crate::Key;
 
 
 /// A list that does not reuse or remove indices upon deletion. The exception is the `pop`
-/// function. This function removes the last item from the backing list since it does not affect
-/// the indices of the other items. Does not allow insertion of items to avoid messing up the order
-/// of the other items. If you don't need removal, [`crate::keyed_vec::KeyedVec`] works the
-/// similarly.
-#[derive(Debug)]
+/// function. This function removes the last item (and any holes after it) from the backing list
+/// since it does not affect the indices of the other items. Items can't be inserted between others
+/// since that would shift their indices, but [`Self::insert_at`] can fill a hole or a key past
+/// the end. If you don't need removal, [`crate::keyed_vec::KeyedVec`] works similarly.
+#[derive(Debug, Clone)]
 pub struct SparseList<K: Key, T> {
     inner: Vec<Option<T>>,
     used_count: usize,
+    /// Set by `IndexMut`, since writes through it can fill or empty slots behind our back. The
+    /// count is redone the next time it's needed.
+    count_stale: bool,
     _phantom: PhantomData<K>,
 }
 #[allow(dead_code)]
@@ -39,32 +56,233 @@ impl<K: Key, T> SparseList<K, T> {
         SparseList {
             inner: Vec::new(),
             used_count: 0,
+            count_stale: false,
             _phantom: PhantomData,
         }
     }
-    
+
+    /// Redoes the used count if a write through `IndexMut` might have changed it
+    fn sync_count(&mut self) {
+        if self.count_stale {
+            self.used_count = self.count_used_slots();
+            self.count_stale = false;
+        }
+    }
+
+    fn count_used_slots(&self)->usize {
+        self.inner.iter().filter(|o|o.is_some()).count()
+    }
+
+    /// Creates a list that compacts itself whenever more than `max_hole_ratio` (`0.0..=1.0`) of
+    /// its slots are holes. `remap` is called with `(old_key, new_key)` for every item moved by a
+    /// compaction, so references stored elsewhere can be fixed up.
+    pub fn with_auto_compact<F: FnMut(K, K)>(max_hole_ratio: f32, remap: F)->AutoCompactSparseList<K, T, F> {
+        AutoCompactSparseList {
+            list: Self::new(),
+            max_hole_ratio,
+            remap,
+        }
+    }
+
+    /// Creates a new list with room for at least `capacity` slots
+    pub fn with_capacity(capacity: usize)->Self {
+        SparseList {
+            inner: Vec::with_capacity(capacity),
+            used_count: 0,
+            count_stale: false,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Makes room for at least `additional` more slots without reallocating
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional);
+    }
+
+    /// Like [`FromIterator::from_iter`], but also returns the key of each item in order
+    pub fn from_iter_with_keys<I: IntoIterator<Item = T>>(iter: I)->(Self, Vec<K>) {
+        let mut list = Self::new();
+        let keys = iter.into_iter().map(|data|list.push(data)).collect();
+
+        return (list, keys);
+    }
+
     pub fn push(&mut self, data: T)->K {
+        self.sync_count();
         self.inner.push(Some(data));
         self.used_count += 1;
         return K::from_id(self.inner.len() - 1);
     }
 
-    pub fn remove(&mut self, index: usize)->Option<T> {
-        self.used_count -= 1;
-        self.inner[index].take()
+    /// Puts the data at exactly the given key, growing the list with holes if the key is past the
+    /// end. Returns the data that was there before, if any. No other keys are affected.
+    pub fn insert_at(&mut self, key: K, data: T)->Option<T> {
+        self.sync_count();
+        let id = key.id();
+        if id >= self.inner.len() {
+            self.inner.resize_with(id + 1, ||None);
+        }
+
+        let old = self.inner[id].replace(data);
+        if old.is_none() {
+            self.used_count += 1;
+        }
+
+        return old;
     }
 
-    pub fn pop(&mut self)->Option<T> {
+    /// Swaps the contents of two slots, holes included. No other keys are affected.
+    ///
+    /// Panics if either key is past the end of the list.
+    pub fn swap(&mut self, a: K, b: K) {
+        let len = self.inner.len();
+        assert!(a.id() < len && b.id() < len, "Key out of bounds for a SparseList with {} slots", len);
+
+        self.inner.swap(a.id(), b.id());
+    }
+
+    /// Removes the data, leaving a hole. Returns `None` if there was nothing at the key.
+    pub fn remove(&mut self, key: K)->Option<T> {
+        self.sync_count();
+        let data = self.inner.get_mut(key.id())?.take()?;
         self.used_count -= 1;
-        self.inner.pop().flatten()
+
+        return Some(data);
+    }
+
+    pub fn get(&self, key: K)->Option<&T> {
+        self.inner.get(key.id())?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, key: K)->Option<&mut T> {
+        self.inner.get_mut(key.id())?.as_mut()
+    }
+
+    /// Removes the last item along with any holes after it. Returns `None` if the list has no
+    /// data, in which case all the slots are removed.
+    pub fn pop(&mut self)->Option<(K, T)> {
+        self.sync_count();
+        while let Some(slot) = self.inner.pop() {
+            if let Some(data) = slot {
+                self.used_count -= 1;
+                return Some((K::from_id(self.inner.len()), data));
+            }
+        }
+
+        return None;
+    }
+
+    /// Consumes the list and yields all the data with its key
+    pub fn into_iter_with_keys(self)->SparseListIntoIterWithKeys<K, T> {
+        SparseListIntoIterWithKeys {
+            remaining: self.used_count(),
+            inner: self.inner.into_iter().enumerate(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Removes all the data that does not match the predicate, leaving holes in their place
+    pub fn retain<F: FnMut(K, &mut T)->bool>(&mut self, mut f: F) {
+        self.sync_count();
+        for (i, slot) in self.inner.iter_mut().enumerate() {
+            let Some(data) = slot else {continue};
+
+            if !f(K::from_id(i), data) {
+                *slot = None;
+                self.used_count -= 1;
+            }
+        }
+    }
+
+    /// Moves all the data to the front to fill the holes and drops the empty slots left at the end.
+    /// Returns `(old_key, new_key)` for every item that moved, in order. Keys that are not in the
+    /// list stay the same.
+    pub fn compact(&mut self)->Vec<(K, K)> {
+        let mut moved = Vec::new();
+        let mut next = 0;
+        for i in 0..self.inner.len() {
+            if self.inner[i].is_none() {continue}
+
+            if i != next {
+                self.inner.swap(i, next);
+                moved.push((K::from_id(i), K::from_id(next)));
+            }
+            next += 1;
+        }
+        self.inner.truncate(next);
+
+        return moved;
+    }
+
+    /// Moves all the slots of `other` (holes included) onto the end of this list. Returns the
+    /// offset that translates `other`'s keys into keys in this list.
+    pub fn append(&mut self, other: SparseList<K, T>)->KeyOffset {
+        self.sync_count();
+        let offset = self.inner.len();
+        self.used_count += other.used_count();
+        self.inner.extend(other.inner);
+
+        return KeyOffset(offset);
+    }
+
+    /// Removes all the data and slots, but keeps the allocation around
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        self.used_count = 0;
+        self.count_stale = false;
+    }
+
+    /// Removes and yields all the data with its key. The list is empty afterwards, but keeps its
+    /// allocation.
+    pub fn drain<'a>(&'a mut self)->SparseListDrain<'a, K, T> {
+        self.sync_count();
+        let remaining = self.used_count;
+        self.used_count = 0;
+
+        SparseListDrain {
+            inner: self.inner.drain(..).enumerate(),
+            remaining,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Drops trailing empty slots and releases unused capacity. Never touches occupied slots, so
+    /// [`Self::used_count`] stays the same. Returns `true` if the backing store actually shrank.
+    pub fn shrink_to_fit(&mut self)->bool {
+        let old_len = self.inner.len();
+        let old_capacity = self.inner.capacity();
+
+        while let Some(None) = self.inner.last() {
+            self.inner.pop();
+        }
+        self.inner.shrink_to_fit();
+
+        return self.inner.len() != old_len || self.inner.capacity() != old_capacity;
     }
 
     pub fn iter<'a>(&'a self)->SparseListIter<'a, T> {
-        SparseListIter(self.inner.iter())
+        SparseListIter {
+            inner: self.inner.iter(),
+            remaining: self.used_count(),
+        }
+    }
+
+    /// If there is data at the key. Keys past the end are fine, they just aren't in the list.
+    pub fn contains_key(&self, key: K)->bool {
+        self.get(key).is_some()
+    }
+
+    /// If there is no data in the list. There may still be empty slots.
+    pub fn is_empty(&self)->bool {
+        self.used_count() == 0
     }
 
     pub fn used_count(&self)->usize {
-        self.used_count
+        if self.count_stale {
+            return self.count_used_slots();
+        }
+
+        return self.used_count;
     }
 
     pub fn slot_count(&self)->usize {
@@ -72,45 +290,114 @@ impl<K: Key, T> SparseList<K, T> {
     }
 
     pub fn iter_mut<'a>(&'a mut self)->SparseListIterMut<'a, T> {
-        SparseListIterMut(self.inner.iter_mut())
+        self.sync_count();
+        SparseListIterMut {
+            inner: self.inner.iter_mut(),
+            remaining: self.used_count,
+        }
     }
 
     pub fn iter_keys<'a>(&'a self)->SparseListIterKeys<'a, K, T> {
         SparseListIterKeys {
             inner:self.inner.iter().enumerate(),
+            remaining: self.used_count(),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn iter_with_keys<'a>(&'a self)->SparseListIterWithKeys<'a, K, T> {
+        SparseListIterWithKeys {
+            inner: self.inner.iter().enumerate(),
+            remaining: self.used_count(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Iterate over every slot, including the holes
+    pub fn iter_slots<'a>(&'a self)->SparseListIterSlots<'a, K, T> {
+        SparseListIterSlots {
+            inner: self.inner.iter().enumerate(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Iterate over the keys of the holes
+    pub fn holes<'a>(&'a self)->SparseListHoles<'a, K, T> {
+        SparseListHoles {
+            inner: self.inner.iter().enumerate(),
+            remaining: self.inner.len() - self.used_count(),
             _phantom: PhantomData,
         }
     }
 
     pub fn iter_mut_with_keys<'a>(&'a mut self)->SparseListIterMutKeys<'a, K, T> {
+        self.sync_count();
         SparseListIterMutKeys {
             inner: self.inner.iter_mut().enumerate(),
+            remaining: self.used_count,
             _phantom: PhantomData,
         }
     }
 }
+impl<K: Key, T> Default for SparseList<K, T> {
+    fn default()->Self {
+        Self::new()
+    }
+}
 impl<K: Key, T> Index<K> for SparseList<K, T> {
     type Output = Option<T>;
     fn index(&self, index: K)->&Self::Output {
         &self.inner[index.id()]
     }
 }
+/// Since the slot can be filled or emptied through this, the used count is redone the next time
+/// it's needed. That is a scan over all the slots, so prefer `insert_at` and `remove`.
 impl<K: Key, T> IndexMut<K> for SparseList<K, T> {
     fn index_mut(&mut self, index: K)->&mut Self::Output {
+        self.count_stale = true;
         &mut self.inner[index.id()]
     }
 }
+/// Serializes as the list of slots, with `None` for holes
+impl<K: Key, T: Serialize> Serialize for SparseList<K, T> {
+    fn serialize<S: Serializer>(&self, serializer: S)->Result<S::Ok, S::Error> {
+        self.inner.serialize(serializer)
+    }
+}
+/// The used count is recomputed from the slots, so it can never disagree with them
+impl<'de, K: Key, T: Deserialize<'de>> Deserialize<'de> for SparseList<K, T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)->Result<Self, D::Error> {
+        let inner = Vec::<Option<T>>::deserialize(deserializer)?;
+        let used_count = inner.iter().filter(|o|o.is_some()).count();
+
+        return Ok(SparseList {
+            inner,
+            used_count,
+            count_stale: false,
+            _phantom: PhantomData,
+        });
+    }
+}
 impl<K: Key, T> IntoIterator for SparseList<K, T> {
     type Item = T;
     type IntoIter = SparseListIntoIter<T>;
     fn into_iter(self)->Self::IntoIter {
         SparseListIntoIter {
+            remaining: self.used_count(),
             inner: self.inner.into_iter(),
         }
     }
 }
+impl<K: Key, T> FromIterator<T> for SparseList<K, T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I)->Self {
+        let mut list = Self::new();
+        list.extend(iter);
+        return list;
+    }
+}
 impl<K: Key, T> Extend<T> for SparseList<K, T> {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.sync_count();
         let starting_len = self.inner.len();
         self.inner.extend(iter.into_iter().map(Option::Some));
         let added_count = self.inner.len() - starting_len;
@@ -119,26 +406,130 @@ impl<K: Key, T> Extend<T> for SparseList<K, T> {
     }
 }
 
-pub struct SparseListIter<'a, T: 'a>(Iter<'a, Option<T>>);
-impl<'a, T> Iterator for SparseListIter<'a, T> {
+/// A [`SparseList`] that compacts itself when it has too many holes. See
+/// [`SparseList::with_auto_compact`]. Read-only access to the list is available through `Deref`.
+pub struct AutoCompactSparseList<K: Key, T, F: FnMut(K, K)> {
+    list: SparseList<K, T>,
+    max_hole_ratio: f32,
+    remap: F,
+}
+impl<K: Key, T, F: FnMut(K, K)> AutoCompactSparseList<K, T, F> {
+    /// Compacts the list if there are too many holes. Returns `true` if it did.
+    pub fn maybe_compact(&mut self)->bool {
+        let slots = self.list.slot_count();
+        if slots == 0 {return false}
+
+        let holes = slots.saturating_sub(self.list.used_count());
+        if holes as f32 / slots as f32 <= self.max_hole_ratio {return false}
+
+        for (old, new) in self.list.compact() {
+            (self.remap)(old, new);
+        }
+
+        return true;
+    }
+
+    pub fn push(&mut self, data: T)->K {
+        self.list.push(data)
+    }
+
+    /// Removes the data, which might trigger a compaction
+    pub fn remove(&mut self, key: K)->Option<T> {
+        let data = self.list.remove(key)?;
+        self.maybe_compact();
+
+        return Some(data);
+    }
+
+    pub fn pop(&mut self)->Option<(K, T)> {
+        let data = self.list.pop();
+        self.maybe_compact();
+
+        return data;
+    }
+
+    /// Removes all the data that does not match the predicate, which might trigger a compaction
+    pub fn retain<P: FnMut(K, &mut T)->bool>(&mut self, f: P) {
+        self.list.retain(f);
+        self.maybe_compact();
+    }
+
+    pub fn get_mut(&mut self, key: K)->Option<&mut T> {
+        self.list.get_mut(key)
+    }
+
+    pub fn into_inner(self)->SparseList<K, T> {
+        self.list
+    }
+}
+impl<K: Key, T, F: FnMut(K, K)> Deref for AutoCompactSparseList<K, T, F> {
+    type Target = SparseList<K, T>;
+    fn deref(&self)->&SparseList<K, T> {
+        &self.list
+    }
+}
+
+pub struct SparseListIter<'a, T: 'a> {
+    inner: Iter<'a, Option<T>>,
+    remaining: usize,
+}
+impl<'a, T: 'a> Iterator for SparseListIter<'a, T> {
     type Item = &'a T;
-    fn next(&mut self)->Option<&'a T> {
-        while let Some(i) = self.0.next() {
+    fn next(&mut self)->Option<Self::Item> {
+        while let Some(i) = self.inner.next() {
             if i.is_some() {
+                self.remaining -= 1;
                 return i.as_ref();
             }
         }
 
         return None;
     }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
 }
+impl<'a, T: 'a> DoubleEndedIterator for SparseListIter<'a, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        while let Some(i) = self.inner.next_back() {
+            if i.is_some() {
+                self.remaining -= 1;
+                return i.as_ref();
+            }
+        }
 
-pub struct SparseListIterMut<'a, T: 'a>(IterMut<'a, Option<T>>);
+        return None;
+    }
+}
+impl<'a, T: 'a> ExactSizeIterator for SparseListIter<'a, T> {}
+
+pub struct SparseListIterMut<'a, T: 'a> {
+    inner: IterMut<'a, Option<T>>,
+    remaining: usize,
+}
 impl<'a, T: 'a> Iterator for SparseListIterMut<'a, T> {
     type Item = &'a mut T;
-    fn next(&mut self)->Option<&'a mut T> {
-        while let Some(i) = self.0.next() {
+    fn next(&mut self)->Option<Self::Item> {
+        while let Some(i) = self.inner.next() {
+            if i.is_some() {
+                self.remaining -= 1;
+                return i.as_mut();
+            }
+        }
+
+        return None;
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<'a, T: 'a> DoubleEndedIterator for SparseListIterMut<'a, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        while let Some(i) = self.inner.next_back() {
             if i.is_some() {
+                self.remaining -= 1;
                 return i.as_mut();
             }
         }
@@ -146,26 +537,83 @@ impl<'a, T: 'a> Iterator for SparseListIterMut<'a, T> {
         return None;
     }
 }
+impl<'a, T: 'a> ExactSizeIterator for SparseListIterMut<'a, T> {}
 
 pub struct SparseListIterKeys<'a, K: Key, T: 'a> {
     inner: Enumerate<Iter<'a, Option<T>>>,
+    remaining: usize,
     _phantom: PhantomData<K>,
 }
 impl<'a, K: Key, T: 'a> Iterator for SparseListIterKeys<'a, K, T> {
     type Item = K;
-    fn next(&mut self)->Option<K> {
+    fn next(&mut self)->Option<Self::Item> {
         while let Some((i, t)) = self.inner.next() {
             if t.is_some() {
+                self.remaining -= 1;
                 return Some(K::from_id(i));
             }
         }
 
         return None;
     }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<'a, K: Key, T: 'a> DoubleEndedIterator for SparseListIterKeys<'a, K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        while let Some((i, t)) = self.inner.next_back() {
+            if t.is_some() {
+                self.remaining -= 1;
+                return Some(K::from_id(i));
+            }
+        }
+
+        return None;
+    }
+}
+impl<'a, K: Key, T: 'a> ExactSizeIterator for SparseListIterKeys<'a, K, T> {}
+
+pub struct SparseListIterWithKeys<'a, K: Key, T: 'a> {
+    inner: Enumerate<Iter<'a, Option<T>>>,
+    remaining: usize,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, T: 'a> Iterator for SparseListIterWithKeys<'a, K, T> {
+    type Item = (K, &'a T);
+    fn next(&mut self)->Option<Self::Item> {
+        while let Some((i, o_t)) = self.inner.next() {
+            if let Some(t) = o_t {
+                self.remaining -= 1;
+                return Some((K::from_id(i), t));
+            }
+        }
+
+        return None;
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
 }
+impl<'a, K: Key, T: 'a> DoubleEndedIterator for SparseListIterWithKeys<'a, K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        while let Some((i, o_t)) = self.inner.next_back() {
+            if let Some(t) = o_t {
+                self.remaining -= 1;
+                return Some((K::from_id(i), t));
+            }
+        }
+
+        return None;
+    }
+}
+impl<'a, K: Key, T: 'a> ExactSizeIterator for SparseListIterWithKeys<'a, K, T> {}
 
 pub struct SparseListIterMutKeys<'a, K: Key, T: 'a> {
     inner: Enumerate<IterMut<'a, Option<T>>>,
+    remaining: usize,
     _phantom: PhantomData<K>,
 }
 impl<'a, K: Key, T: 'a> Iterator for SparseListIterMutKeys<'a, K, T> {
@@ -173,22 +621,58 @@ impl<'a, K: Key, T: 'a> Iterator for SparseListIterMutKeys<'a, K, T> {
     fn next(&mut self)->Option<Self::Item> {
         while let Some((i, o_t)) = self.inner.next() {
             if let Some(t) = o_t {
+                self.remaining -= 1;
                 return Some((K::from_id(i), t));
             }
         }
 
         return None;
     }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
 }
+impl<'a, K: Key, T: 'a> DoubleEndedIterator for SparseListIterMutKeys<'a, K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        while let Some((i, o_t)) = self.inner.next_back() {
+            if let Some(t) = o_t {
+                self.remaining -= 1;
+                return Some((K::from_id(i), t));
+            }
+        }
+
+        return None;
+    }
+}
+impl<'a, K: Key, T: 'a> ExactSizeIterator for SparseListIterMutKeys<'a, K, T> {}
 
 pub struct SparseListIntoIter<T> {
     inner: std::vec::IntoIter<Option<T>>,
+    remaining: usize,
 }
 impl<T> Iterator for SparseListIntoIter<T> {
     type Item = T;
-    fn next(&mut self)->Option<T> {
+    fn next(&mut self)->Option<Self::Item> {
         while let Some(item) = self.inner.next() {
             if item.is_some() {
+                self.remaining -= 1;
+                return item;
+            }
+        }
+
+        return None;
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<T> DoubleEndedIterator for SparseListIntoIter<T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        while let Some(item) = self.inner.next_back() {
+            if item.is_some() {
+                self.remaining -= 1;
                 return item;
             }
         }
@@ -196,3 +680,180 @@ impl<T> Iterator for SparseListIntoIter<T> {
         return None;
     }
 }
+impl<T> ExactSizeIterator for SparseListIntoIter<T> {}
+
+pub struct SparseListIntoIterWithKeys<K: Key, T> {
+    inner: Enumerate<std::vec::IntoIter<Option<T>>>,
+    remaining: usize,
+    _phantom: PhantomData<K>,
+}
+impl<K: Key, T> Iterator for SparseListIntoIterWithKeys<K, T> {
+    type Item = (K, T);
+    fn next(&mut self)->Option<Self::Item> {
+        while let Some((i, o_t)) = self.inner.next() {
+            if let Some(t) = o_t {
+                self.remaining -= 1;
+                return Some((K::from_id(i), t));
+            }
+        }
+
+        return None;
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<K: Key, T> DoubleEndedIterator for SparseListIntoIterWithKeys<K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        while let Some((i, o_t)) = self.inner.next_back() {
+            if let Some(t) = o_t {
+                self.remaining -= 1;
+                return Some((K::from_id(i), t));
+            }
+        }
+
+        return None;
+    }
+}
+impl<K: Key, T> ExactSizeIterator for SparseListIntoIterWithKeys<K, T> {}
+
+pub struct SparseListDrain<'a, K: Key, T: 'a> {
+    inner: Enumerate<Drain<'a, Option<T>>>,
+    remaining: usize,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, T: 'a> Iterator for SparseListDrain<'a, K, T> {
+    type Item = (K, T);
+    fn next(&mut self)->Option<Self::Item> {
+        while let Some((i, o_t)) = self.inner.next() {
+            if let Some(t) = o_t {
+                self.remaining -= 1;
+                return Some((K::from_id(i), t));
+            }
+        }
+
+        return None;
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<'a, K: Key, T: 'a> DoubleEndedIterator for SparseListDrain<'a, K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        while let Some((i, o_t)) = self.inner.next_back() {
+            if let Some(t) = o_t {
+                self.remaining -= 1;
+                return Some((K::from_id(i), t));
+            }
+        }
+
+        return None;
+    }
+}
+impl<'a, K: Key, T: 'a> ExactSizeIterator for SparseListDrain<'a, K, T> {}
+
+pub struct SparseListIterSlots<'a, K: Key, T: 'a> {
+    inner: Enumerate<Iter<'a, Option<T>>>,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, T: 'a> Iterator for SparseListIterSlots<'a, K, T> {
+    type Item = (K, Option<&'a T>);
+    fn next(&mut self)->Option<Self::Item> {
+        self.inner.next().map(|(i, o_t)|(K::from_id(i), o_t.as_ref()))
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+impl<'a, K: Key, T: 'a> DoubleEndedIterator for SparseListIterSlots<'a, K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        self.inner.next_back().map(|(i, o_t)|(K::from_id(i), o_t.as_ref()))
+    }
+}
+impl<'a, K: Key, T: 'a> ExactSizeIterator for SparseListIterSlots<'a, K, T> {}
+
+pub struct SparseListHoles<'a, K: Key, T: 'a> {
+    inner: Enumerate<Iter<'a, Option<T>>>,
+    remaining: usize,
+    _phantom: PhantomData<K>,
+}
+impl<'a, K: Key, T: 'a> Iterator for SparseListHoles<'a, K, T> {
+    type Item = K;
+    fn next(&mut self)->Option<Self::Item> {
+        while let Some((i, t)) = self.inner.next() {
+            if t.is_none() {
+                self.remaining -= 1;
+                return Some(K::from_id(i));
+            }
+        }
+
+        return None;
+    }
+
+    fn size_hint(&self)->(usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl<'a, K: Key, T: 'a> DoubleEndedIterator for SparseListHoles<'a, K, T> {
+    fn next_back(&mut self)->Option<Self::Item> {
+        while let Some((i, t)) = self.inner.next_back() {
+            if t.is_none() {
+                self.remaining -= 1;
+                return Some(K::from_id(i));
+            }
+        }
+
+        return None;
+    }
+}
+impl<'a, K: Key, T: 'a> ExactSizeIterator for SparseListHoles<'a, K, T> {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_mut_writes_dont_break_iterators() {
+        let mut list = SparseList::<usize, i32>::new();
+        let a = list.push(1);
+        list.remove(a);
+        list[a] = Some(5);
+
+        assert_eq!(list.used_count(), 1);
+        assert_eq!(list.iter().len(), 1);
+        assert_eq!(list.iter().rev().collect::<Vec<_>>(), [&5]);
+        assert_eq!(list.holes().len(), 0);
+
+        list[a] = None;
+        assert_eq!(list.iter_mut().len(), 0);
+        assert_eq!(list.holes().len(), 1);
+
+        list[a] = Some(6);
+        list.retain(|_, _|false);
+        assert_eq!(list.used_count(), 0);
+        assert_eq!(list.iter_with_keys().len(), 0);
+    }
+
+    #[test]
+    fn shrink_to_fit_keeps_data() {
+        let mut list = SparseList::<usize, i32>::new();
+        let a = list.push(1);
+        let b = list.push(2);
+        let c = list.push(3);
+        list.remove(b);
+        list.remove(c);
+
+        assert!(list.shrink_to_fit());
+        assert_eq!(list.used_count(), 1);
+        assert_eq!(list.slot_count(), 1);
+        assert_eq!(list.get(a), Some(&1));
+
+        assert!(!list.shrink_to_fit());
+        assert_eq!(list.used_count(), 1);
+        assert_eq!(list.slot_count(), 1);
+    }
+}
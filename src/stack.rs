@@ -1,80 +1,560 @@
-use serde::{Serialize, Deserialize};
+use serde::{
+    Serialize,
+    Serializer,
+    Deserialize,
+};
 use std::{
     ops::{
         Index,
         IndexMut,
         RangeBounds,
     },
-    vec::Drain,
+    vec::{
+        Drain,
+        IntoIter,
+    },
+    iter::Rev,
+    fmt::{
+        self,
+        Display,
+        Formatter,
+    },
+    sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    },
 };
 
 
+static NEXT_STACK_ID: AtomicUsize = AtomicUsize::new(0);
+
+
+/// Identifies a stack so checkpoints can't be rolled back on the wrong one. Every stack (and every
+/// clone of one) gets a new id. Ids are ignored when comparing stacks.
+#[derive(Debug)]
+struct StackId(usize);
+impl Default for StackId {
+    fn default()->Self {
+        StackId(NEXT_STACK_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+impl Clone for StackId {
+    fn clone(&self)->Self {
+        Self::default()
+    }
+}
+impl PartialEq for StackId {
+    fn eq(&self, _: &Self)->bool {
+        true
+    }
+}
+
+
+/// A saved height of a [`Stack`], to be rolled back to with [`Stack::rollback_to`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackCheckpoint {
+    stack: usize,
+    len: usize,
+    frames: usize,
+}
+
+
+/// The stack had fewer items than an operation needed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackUnderflow {
+    /// How many items the operation needed
+    pub needed: usize,
+    /// How many items the stack had
+    pub len: usize,
+}
+impl Display for StackUnderflow {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        write!(f, "stack underflow: needed {} items, but there were {}", self.needed, self.len)
+    }
+}
+impl std::error::Error for StackUnderflow {}
+
+/// Pushing would have made the stack deeper than its limit. Holds the item that wasn't pushed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackOverflow<T> {
+    pub item: T,
+    pub limit: usize,
+}
+impl<T> Display for StackOverflow<T> {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        write!(f, "stack overflow: the stack is limited to {} items", self.limit)
+    }
+}
+impl<T: fmt::Debug> std::error::Error for StackOverflow<T> {}
+
+/// Returned by operations that both need items on the stack and push new ones, like
+/// [`Stack::pick`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackError {
+    Underflow(StackUnderflow),
+    /// Pushing would have gone past the limit. The item was a copy, so it isn't returned.
+    Overflow {
+        limit: usize,
+    },
+}
+impl From<StackUnderflow> for StackError {
+    fn from(e: StackUnderflow)->Self {
+        StackError::Underflow(e)
+    }
+}
+impl Display for StackError {
+    fn fmt(&self, f: &mut Formatter)->fmt::Result {
+        match self {
+            StackError::Underflow(e)=>e.fmt(f),
+            StackError::Overflow{limit}=>{
+                write!(f, "stack overflow: the stack is limited to {} items", limit)
+            },
+        }
+    }
+}
+impl std::error::Error for StackError {}
+
+
+/// The serialized form of a [`Stack`]
+#[derive(Deserialize)]
+#[serde(rename = "Stack")]
+struct StackData<T>(Vec<T>);
+
+
 /// A simple stack data structure. Implements [`Index`] and [`IndexMut`] to index from the top of
-/// the stack downward.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Stack<T>(Vec<T>);
+/// the stack downward, panicking past the bottom of the stack. Use [`Self::get`] to check instead.
+///
+/// The stack can be split into frames with [`Self::push_frame`], for things like function calls in
+/// an interpreter. Frames only mark where they start; all the normal operations still see the
+/// whole stack.
+///
+/// Serializes as just the items, like it did before frames and limits existed. The frames and the
+/// limit are not serialized, so a deserialized stack has neither.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "StackData<T>")]
+pub struct Stack<T> {
+    items: Vec<T>,
+    /// The length of the stack when each frame was pushed
+    frames: Vec<usize>,
+    /// The maximum number of items, if there is one
+    limit: Option<usize>,
+    id: StackId,
+}
+impl<T> From<StackData<T>> for Stack<T> {
+    fn from(data: StackData<T>)->Self {
+        Stack {
+            items: data.0,
+            frames: Vec::new(),
+            limit: None,
+            id: StackId::default(),
+        }
+    }
+}
+impl<T: Serialize> Serialize for Stack<T> {
+    fn serialize<S: Serializer>(&self, serializer: S)->Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct("Stack", &self.items)
+    }
+}
 impl<T> Stack<T> {
     /// Creates a new stack
     pub fn new()->Self {
-        Stack(Vec::with_capacity(4))
+        Self::with_capacity(4)
     }
 
-    /// Clears the stack
+    /// Clears the stack, including the frames
     pub fn clear(&mut self) {
-        self.0.clear();
+        self.items.clear();
+        self.frames.clear();
     }
 
-    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R)->Drain<T> {
-        self.0.drain(range)
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R)->Drain<'_, T> {
+        self.items.drain(range)
     }
 
     /// Creates a new stack with at least `capacity` empty slots
     pub fn with_capacity(capacity: usize)->Self {
-        Stack(Vec::with_capacity(capacity.max(4)))
+        Stack {
+            items: Vec::with_capacity(capacity.max(4)),
+            frames: Vec::new(),
+            limit: None,
+            id: StackId::default(),
+        }
+    }
+
+    /// Creates a new stack that can hold at most `limit` items. [`Self::try_push`] fails once the
+    /// limit is reached and the other pushing operations panic.
+    pub fn with_limit(limit: usize)->Self {
+        let mut stack = Self::new();
+        stack.limit = Some(limit);
+        return stack;
+    }
+
+    pub fn limit(&self)->Option<usize> {
+        self.limit
+    }
+
+    /// Saves the current height and frames of the stack so they can be restored with
+    /// [`Self::rollback_to`]
+    pub fn checkpoint(&self)->StackCheckpoint {
+        StackCheckpoint {
+            stack: self.id.0,
+            len: self.items.len(),
+            frames: self.frames.len(),
+        }
+    }
+
+    /// Pops all the items and frames pushed since the checkpoint. The checkpoint must come from
+    /// this stack, and the stack must not have been popped below it since. Both are checked in
+    /// debug builds.
+    pub fn rollback_to(&mut self, checkpoint: StackCheckpoint) {
+        debug_assert_eq!(checkpoint.stack, self.id.0, "StackCheckpoint is from a different stack");
+        debug_assert!(
+            self.items.len() >= checkpoint.len && self.frames.len() >= checkpoint.frames,
+            "Stack was popped below the checkpoint",
+        );
+
+        self.items.truncate(checkpoint.len);
+        self.frames.truncate(checkpoint.frames);
+    }
+
+    /// Starts a new frame at the current top of the stack
+    pub fn push_frame(&mut self) {
+        self.frames.push(self.items.len());
+    }
+
+    /// Removes the current frame and returns its items, bottom first. Returns `None` if there are
+    /// no frames.
+    pub fn pop_frame(&mut self)->Option<Vec<T>> {
+        let start = self.frames.pop()?;
+        let start = start.min(self.items.len());
+
+        return Some(self.items.split_off(start));
     }
 
-    /// Pushes the item onto the stack
+    /// The number of items in the current frame, or the whole stack if there are no frames
+    pub fn frame_len(&self)->usize {
+        self.items.len() - self.frame_start()
+    }
+
+    /// The number of frames that have been pushed
+    pub fn frame_count(&self)->usize {
+        self.frames.len()
+    }
+
+    /// Iterate from the top of the stack to the bottom of the current frame
+    pub fn iter_frame<'a>(&'a self)->impl 'a + Iterator<Item = &'a T> {
+        self.items[self.frame_start()..].iter().rev()
+    }
+
+    /// Where the current frame starts in `items`. Items popped below the start of the frame make
+    /// it empty instead of going negative.
+    fn frame_start(&self)->usize {
+        match self.frames.last() {
+            Some(start)=>(*start).min(self.items.len()),
+            None=>0,
+        }
+    }
+
+    /// Pushes the item onto the stack. Returns the item back if the stack is at its limit.
+    pub fn try_push(&mut self, item: T)->Result<(), StackOverflow<T>> {
+        if let Some(limit) = self.limit {
+            if self.items.len() >= limit {
+                return Err(StackOverflow {
+                    item,
+                    limit,
+                });
+            }
+        }
+
+        self.items.push(item);
+        return Ok(());
+    }
+
+    /// Pushes the item onto the stack or panics if the stack is at its limit
     pub fn push(&mut self, item: T) {
-        self.0.push(item);
+        if let Err(e) = self.try_push(item) {
+            panic!("Stack overflow: the stack is limited to {} items", e.limit);
+        }
     }
 
     /// Pops the last item or panics if there are no items
     pub fn pop(&mut self)->Option<T> {
-        self.0.pop()
+        self.items.pop()
+    }
+
+    /// Pops the top `n` items, with the top of the stack last (the order they were pushed in).
+    /// Returns `None` and leaves the stack alone if there are fewer than `n` items.
+    pub fn pop_n(&mut self, n: usize)->Option<Vec<T>> {
+        let start = self.items.len().checked_sub(n)?;
+        return Some(self.items.split_off(start));
+    }
+
+    /// Same as [`Self::pop_n`], but returns an array
+    pub fn pop_array<const N: usize>(&mut self)->Option<[T; N]> {
+        let items = self.pop_n(N)?;
+        return items.try_into().ok();
+    }
+
+    /// Removes the top `n` items, yielding them in the order they were pushed. Returns `None` and
+    /// leaves the stack alone if there are fewer than `n` items.
+    pub fn drain_top(&mut self, n: usize)->Option<Drain<'_, T>> {
+        let start = self.items.len().checked_sub(n)?;
+        return Some(self.items.drain(start..));
+    }
+
+    /// Pops items until there are at most `len` left. Frames are not popped, so any that started
+    /// above `len` are left empty.
+    pub fn truncate_to(&mut self, len: usize) {
+        self.items.truncate(len);
+    }
+
+    /// Moves the top `n` items into a new stack, keeping their order. Returns `None` and leaves
+    /// the stack alone if there are fewer than `n` items.
+    pub fn split_off_top(&mut self, n: usize)->Option<Stack<T>> {
+        let items = self.pop_n(n)?;
+        return Some(Stack {
+            items,
+            frames: Vec::new(),
+            limit: self.limit,
+            id: StackId::default(),
+        });
+    }
+
+    fn check_len(&self, needed: usize)->Result<(), StackUnderflow> {
+        if self.items.len() < needed {
+            return Err(StackUnderflow {
+                needed,
+                len: self.items.len(),
+            });
+        }
+
+        return Ok(());
+    }
+
+    /// Pushes a copy of the top item: `a -> a a`
+    pub fn dup(&mut self)->Result<(), StackError> where T: Clone {
+        self.pick(0)
+    }
+
+    /// Swaps the top two items: `a b -> b a`
+    pub fn swap(&mut self)->Result<(), StackUnderflow> {
+        self.check_len(2)?;
+        let len = self.items.len();
+        self.items.swap(len - 1, len - 2);
+
+        return Ok(());
+    }
+
+    /// Pushes a copy of the second item: `a b -> a b a`
+    pub fn over(&mut self)->Result<(), StackError> where T: Clone {
+        self.pick(1)
+    }
+
+    /// Moves the third item to the top: `a b c -> b c a`
+    pub fn rot(&mut self)->Result<(), StackUnderflow> {
+        self.check_len(3)?;
+        let len = self.items.len();
+        self.items[len - 3..].rotate_left(1);
+
+        return Ok(());
+    }
+
+    /// Pushes a copy of the item `depth` items down from the top. `pick(0)` is the same as
+    /// [`Self::dup`] and `pick(1)` is the same as [`Self::over`]. Fails if there are too few items
+    /// or the stack is at its limit.
+    pub fn pick(&mut self, depth: usize)->Result<(), StackError> where T: Clone {
+        self.check_len(depth.saturating_add(1))?;
+        let item = self.items[self.items.len() - depth - 1].clone();
+        self.try_push(item).map_err(|e|StackError::Overflow{limit: e.limit})?;
+
+        return Ok(());
+    }
+
+    /// Removes all the items that don't match the predicate, keeping the order of the rest. Frames
+    /// are moved down to account for the items removed below them.
+    pub fn retain<F: FnMut(&T)->bool>(&mut self, f: F) {
+        let keep = self.items.iter().map(f).collect::<Vec<_>>();
+
+        if !self.frames.is_empty() {
+            // The number of items removed below each index
+            let mut removed_below = Vec::with_capacity(keep.len() + 1);
+            removed_below.push(0);
+            for k in keep.iter() {
+                let removed = removed_below.last().unwrap() + (!k) as usize;
+                removed_below.push(removed);
+            }
+
+            for start in self.frames.iter_mut() {
+                *start -= removed_below[(*start).min(keep.len())];
+            }
+        }
+
+        let mut keep = keep.into_iter();
+        self.items.retain(|_|keep.next().unwrap());
     }
 
     /// Gets a reference to the last item or panics if there are none
     pub fn last(&self)->Option<&T> {
-        self.0.last()
+        self.items.last()
     }
 
     /// Gets a mutable reference to the last item or panics if there are none
     pub fn last_mut(&mut self)->Option<&mut T> {
-        self.0.last_mut()
+        self.items.last_mut()
+    }
+
+    /// Gets the item `depth` items down from the top, where `0` is the top. Returns `None` past
+    /// the bottom of the stack.
+    pub fn get(&self, depth: usize)->Option<&T> {
+        let index = self.items.len().checked_sub(depth)?.checked_sub(1)?;
+        return self.items.get(index);
+    }
+
+    pub fn get_mut(&mut self, depth: usize)->Option<&mut T> {
+        let index = self.items.len().checked_sub(depth)?.checked_sub(1)?;
+        return self.items.get_mut(index);
+    }
+
+    /// The top `n` items, with the top of the stack last. Returns `None` if there are fewer than
+    /// `n` items.
+    pub fn peek_n(&self, n: usize)->Option<&[T]> {
+        let start = self.items.len().checked_sub(n)?;
+        return Some(&self.items[start..]);
+    }
+
+    pub fn contains(&self, item: &T)->bool where T: PartialEq {
+        self.items.contains(item)
+    }
+
+    /// The depth from the top of the nearest item matching the predicate
+    pub fn position<F: FnMut(&T)->bool>(&self, f: F)->Option<usize> {
+        self.iter().position(f)
+    }
+
+    /// The nearest item to the top matching the predicate
+    pub fn find<F: FnMut(&T)->bool>(&self, mut f: F)->Option<&T> {
+        self.iter().find(|t|f(t))
+    }
+
+    /// All the items, with the bottom of the stack first
+    pub fn as_slice(&self)->&[T] {
+        &self.items
+    }
+
+    pub fn as_mut_slice(&mut self)->&mut [T] {
+        &mut self.items
+    }
+
+    /// Iterate from the bottom of the stack to the top, in the order the items were pushed.
+    pub fn iter_bottom_up<'a>(&'a self)->std::slice::Iter<'a, T> {
+        self.items.iter()
     }
 
     /// Iterate from the top of the stack to the bottom.
     pub fn iter<'a>(&'a self)->impl 'a + Iterator<Item = &'a T> {
-        self.0.iter().rev()
+        self.items.iter().rev()
     }
 
     /// Iterate from the top of the stack to the bottom, mutably.
     pub fn iter_mut<'a>(&'a mut self)->impl 'a + Iterator<Item = &'a mut T> {
-        self.0.iter_mut().rev()
+        self.items.iter_mut().rev()
+    }
+
+    pub fn len(&self)->usize {self.items.len()}
+
+    pub fn capacity(&self)->usize {
+        self.items.capacity()
     }
 
-    pub fn len(&self)->usize {self.0.len()}
+    /// Releases any memory not needed by the current items
+    pub fn shrink_to_fit(&mut self) {
+        self.items.shrink_to_fit();
+        self.frames.shrink_to_fit();
+    }
+
+    /// The items with the bottom of the stack first. The frames are dropped.
+    pub fn into_vec(self)->Vec<T> {
+        self.items
+    }
+}
+/// Pushes the items in order, so the last one ends up on top
+impl<T> FromIterator<T> for Stack<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I)->Self {
+        let mut stack = Self::new();
+        stack.extend(iter);
+        return stack;
+    }
+}
+/// Panics if the stack reaches its limit, like [`Stack::push`]
+impl<T> Extend<T> for Stack<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+/// Yields the items from the top of the stack to the bottom
+impl<T> IntoIterator for Stack<T> {
+    type Item = T;
+    type IntoIter = Rev<IntoIter<T>>;
+    fn into_iter(self)->Self::IntoIter {
+        self.items.into_iter().rev()
+    }
 }
 impl<T> Index<usize> for Stack<T> {
     type Output = T;
     fn index(&self, index: usize)->&T {
-        let index = self.0.len().saturating_sub(index + 1);
-        &self.0[index]
+        let len = self.items.len();
+        match self.get(index) {
+            Some(item)=>item,
+            None=>panic!("Stack index {} is past the bottom of the stack (len {})", index, len),
+        }
     }
 }
 impl<T> IndexMut<usize> for Stack<T> {
     fn index_mut(&mut self, index: usize)->&mut T {
-        let index = self.0.len().saturating_sub(index + 1);
-        &mut self.0[index]
+        let len = self.items.len();
+        match self.get_mut(index) {
+            Some(item)=>item,
+            None=>panic!("Stack index {} is past the bottom of the stack (len {})", index, len),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_past_the_bottom_is_none() {
+        let mut stack = Stack::new();
+        stack.push(1);
+
+        assert_eq!(stack.get(0), Some(&1));
+        assert_eq!(stack.get(1), None);
+        assert_eq!(stack.get(usize::MAX), None);
+        assert_eq!(stack.get_mut(usize::MAX), None);
+    }
+
+    #[test]
+    fn pick_past_the_bottom_underflows() {
+        let mut stack = Stack::new();
+        stack.push(1);
+
+        assert_eq!(
+            stack.pick(usize::MAX),
+            Err(StackError::Underflow(StackUnderflow {needed: usize::MAX, len: 1})),
+        );
+        assert_eq!(stack.len(), 1);
+    }
+
+    #[test]
+    fn dup_at_the_limit_overflows() {
+        let mut stack = Stack::with_limit(1);
+        stack.push(1);
+
+        assert_eq!(stack.dup(), Err(StackError::Overflow {limit: 1}));
+        assert_eq!(stack.len(), 1);
     }
 }